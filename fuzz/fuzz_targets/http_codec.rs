@@ -0,0 +1,15 @@
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::Decoder;
+use tracing::Span;
+use ziggurat_xrpl::protocol::codecs::http::{HttpCodec, HttpMsg};
+
+fuzz_target!(|data: &[u8]| {
+    // The same input is parsed both as a handshake request and as a handshake response.
+    for expecting in [HttpMsg::Request, HttpMsg::Response] {
+        let mut codec = HttpCodec::new(Span::none(), expecting, Default::default());
+        let _ = codec.decode(&mut BytesMut::from(data));
+    }
+});
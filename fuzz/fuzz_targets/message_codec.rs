@@ -0,0 +1,20 @@
+#![no_main]
+
+use bytes::BytesMut;
+use libfuzzer_sys::fuzz_target;
+use tokio_util::codec::{Decoder, Encoder};
+use tracing::Span;
+use ziggurat_xrpl::protocol::codecs::message::MessageCodec;
+
+fuzz_target!(|data: &[u8]| {
+    let mut codec = MessageCodec::new(Span::none(), Default::default());
+    let mut src = BytesMut::from(data);
+
+    // Decode until the input is either exhausted or rejected; the latter is fine, panics aren't.
+    while let Ok(Some(message)) = codec.decode(&mut src) {
+        // Whatever was decoded has to be encodable again.
+        codec
+            .encode(message.payload, &mut BytesMut::new())
+            .expect("couldn't encode a decoded message");
+    }
+});
@@ -1,20 +1,99 @@
-/// This codec is used during the handshake.
-use std::io;
+//! This codec is used during the handshake.
 
-use bytes::{Buf, Bytes, BytesMut};
-use tokio_util::codec::{BytesCodec, Decoder, Encoder};
+use std::{collections::BTreeMap, io};
+
+use bytes::{Bytes, BytesMut};
+use serde::Deserialize;
+use tokio_util::codec::{Decoder, Encoder};
 use tracing::*;
 
+/// The default maximum number of headers in a handshake message.
+pub const DEFAULT_MAX_HEADERS: usize = 32;
+
+/// The default maximum size of the start line and headers of a handshake message.
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 64 * 1024;
+
+/// The default maximum size of the body of a handshake message, as declared by its
+/// `Content-Length` header.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 64 * 1024;
+
 // The HTTP message type;
 pub enum HttpMsg {
     Request,
     Response,
 }
 
+/// HTTP codec configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct HttpCodecCfg {
+    /// The maximum number of headers a handshake message may contain.
+    pub max_headers: usize,
+    /// The maximum size of the start line and headers, including the terminating empty line.
+    pub max_header_size: usize,
+    /// The maximum size of the body; larger bodies are rejected before being buffered.
+    pub max_body_size: usize,
+}
+
+impl Default for HttpCodecCfg {
+    fn default() -> Self {
+        Self {
+            max_headers: DEFAULT_MAX_HEADERS,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+}
+
+/// The first line of an HTTP message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartLine {
+    Request { method: String, path: String },
+    Response { status: u16, reason: String },
+}
+
+/// A decoded handshake message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpHandshakeMessage {
+    pub start_line: StartLine,
+    /// The minor HTTP/1.x version.
+    pub version: u8,
+    /// The headers keyed by their lowercase names; the values of repeated headers are joined
+    /// with `", "`.
+    pub headers: BTreeMap<String, String>,
+    /// The body, as declared by the `Content-Length` header.
+    pub body: Bytes,
+}
+
+impl HttpHandshakeMessage {
+    /// Returns the value of the given header; header names are case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Returns the status code of a response.
+    pub fn status(&self) -> Option<u16> {
+        match self.start_line {
+            StartLine::Response { status, .. } => Some(status),
+            StartLine::Request { .. } => None,
+        }
+    }
+
+    /// Returns the method of a request.
+    pub fn method(&self) -> Option<&str> {
+        match self.start_line {
+            StartLine::Request { ref method, .. } => Some(method),
+            StartLine::Response { .. } => None,
+        }
+    }
+}
+
 // A codec used to handle HTTP messages.
 pub struct HttpCodec {
-    // The underlying codec.
-    codec: BytesCodec,
+    // The codec configuration.
+    cfg: HttpCodecCfg,
     // The associated node's span.
     span: Span,
     // The next kind of HTTP message expected.
@@ -22,57 +101,128 @@ pub struct HttpCodec {
 }
 
 impl HttpCodec {
-    pub fn new(span: Span, expecting: HttpMsg) -> Self {
+    pub fn new(span: Span, expecting: HttpMsg, cfg: HttpCodecCfg) -> Self {
         HttpCodec {
-            codec: Default::default(),
+            cfg,
             span,
             expecting,
         }
     }
-}
 
-impl Decoder for HttpCodec {
-    type Item = BytesMut;
-    type Error = io::Error;
+    // Parses the start line and headers, returning them along with their size, or `Ok(None)` if
+    // they aren't complete yet. The body is left empty.
+    fn parse_headers(
+        &self,
+        src: &[u8],
+    ) -> Result<Option<(usize, HttpHandshakeMessage)>, httparse::Error> {
+        let mut headers = vec![httparse::EMPTY_HEADER; self.cfg.max_headers];
 
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let mut raw_bytes = if let Some(bytes) = self.codec.decode(src)? {
-            bytes
-        } else {
-            return Ok(None);
-        };
-
-        trace!(parent: &self.span, "got some raw bytes: {:?}", raw_bytes);
-
-        let mut headers = [httparse::EMPTY_HEADER; 16];
-
-        let res = match self.expecting {
+        let (status, start_line, version, parsed_headers) = match self.expecting {
             HttpMsg::Request => {
                 let mut req = httparse::Request::new(&mut headers);
-                req.parse(&raw_bytes)
+                let status = req.parse(src)?;
+                let start_line = StartLine::Request {
+                    method: req.method.unwrap_or_default().to_owned(),
+                    path: req.path.unwrap_or_default().to_owned(),
+                };
+                (status, start_line, req.version, req.headers)
             }
             HttpMsg::Response => {
                 let mut resp = httparse::Response::new(&mut headers);
-                resp.parse(&raw_bytes)
+                let status = resp.parse(src)?;
+                let start_line = StartLine::Response {
+                    status: resp.code.unwrap_or_default(),
+                    reason: resp.reason.unwrap_or_default().to_owned(),
+                };
+                (status, start_line, resp.version, resp.headers)
             }
+        };
+
+        let header_size = match status {
+            httparse::Status::Complete(header_size) => header_size,
+            httparse::Status::Partial => return Ok(None),
+        };
+
+        let mut headers = BTreeMap::<String, String>::new();
+        for header in parsed_headers.iter() {
+            let value = String::from_utf8_lossy(header.value);
+            headers
+                .entry(header.name.to_ascii_lowercase())
+                .and_modify(|values| {
+                    values.push_str(", ");
+                    values.push_str(&value);
+                })
+                .or_insert_with(|| value.into_owned());
         }
-        .map_err(|e| {
+
+        let message = HttpHandshakeMessage {
+            start_line,
+            version: version.unwrap_or_default(),
+            headers,
+            body: Bytes::new(),
+        };
+
+        Ok(Some((header_size, message)))
+    }
+}
+
+impl Decoder for HttpCodec {
+    type Item = HttpHandshakeMessage;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+
+        trace!(parent: &self.span, "got some raw bytes: {:?}", src);
+
+        let parsed = self.parse_headers(src).map_err(|e| {
             error!(parent: &self.span, "HTTP parse error: {}", e);
             io::ErrorKind::InvalidData
         })?;
 
-        match res {
-            httparse::Status::Partial => {
-                // TODO: check if openssl ensures the completeness of requests
-                warn!(parent: &self.span, "unexpected partial HTTP response");
-                Ok(None)
+        let (header_size, mut message) = match parsed {
+            Some(parsed) => parsed,
+            None if src.len() > self.cfg.max_header_size => {
+                error!(parent: &self.span, "HTTP headers exceed {} bytes", self.cfg.max_header_size);
+                return Err(io::ErrorKind::InvalidData.into());
             }
-            httparse::Status::Complete(header_length) => {
-                raw_bytes.advance(header_length);
+            // Wait for the rest of the headers.
+            None => return Ok(None),
+        };
 
-                Ok(Some(raw_bytes))
-            }
+        if header_size > self.cfg.max_header_size {
+            error!(parent: &self.span, "HTTP headers exceed {} bytes", self.cfg.max_header_size);
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        let content_length = match message.header("Content-Length") {
+            Some(length) => length.trim().parse::<usize>().map_err(|e| {
+                error!(parent: &self.span, "invalid Content-Length: {}", e);
+                io::ErrorKind::InvalidData
+            })?,
+            None => 0,
+        };
+
+        if content_length > self.cfg.max_body_size {
+            error!(parent: &self.span, "HTTP body of {} bytes exceeds {} bytes", content_length, self.cfg.max_body_size);
+            return Err(io::ErrorKind::InvalidData.into());
         }
+        let message_size = header_size
+            .checked_add(content_length)
+            .ok_or(io::ErrorKind::InvalidData)?;
+
+        // Wait for the whole body.
+        if src.len() < message_size {
+            src.reserve(message_size - src.len());
+            return Ok(None);
+        }
+
+        let _ = src.split_to(header_size);
+        message.body = src.split_to(content_length).freeze();
+
+        Ok(Some(message))
     }
 }
 
@@ -80,6 +230,116 @@ impl Encoder<Bytes> for HttpCodec {
     type Error = io::Error;
 
     fn encode(&mut self, message: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        self.codec.encode(message, dst)
+        dst.extend_from_slice(&message);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &[u8] = b"HTTP/1.1 101 Switching Protocols\r\n\
+        Connection: Upgrade\r\n\
+        Upgrade: XRPL/2.2\r\n\
+        X-Protocol-Ctl: ledgerreplay=1;txrr=1;\r\n\
+        \r\n";
+
+    fn codec(expecting: HttpMsg) -> HttpCodec {
+        HttpCodec::new(Span::none(), expecting, Default::default())
+    }
+
+    #[test]
+    fn decode_incrementally() {
+        let mut codec = codec(HttpMsg::Response);
+        let mut src = BytesMut::new();
+
+        // Feed the message byte by byte, followed by the first bytes of the next message.
+        for byte in &RESPONSE[..RESPONSE.len() - 1] {
+            src.extend_from_slice(&[*byte]);
+            assert!(codec.decode(&mut src).unwrap().is_none());
+        }
+        src.extend_from_slice(&RESPONSE[RESPONSE.len() - 1..]);
+        src.extend_from_slice(b"\x00\x00");
+
+        let message = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(message.status(), Some(101));
+        assert_eq!(message.version, 1);
+        assert_eq!(
+            message.header("x-protocol-ctl"),
+            Some("ledgerreplay=1;txrr=1;")
+        );
+        assert_eq!(message.header("Upgrade"), Some("XRPL/2.2"));
+        assert!(message.body.is_empty());
+        assert_eq!(&src[..], b"\x00\x00");
+    }
+
+    #[test]
+    fn decode_request_with_body() {
+        let mut codec = codec(HttpMsg::Request);
+        let mut src = BytesMut::from(
+            &b"GET / HTTP/1.1\r\nX-Test: a\r\nx-test: b\r\nContent-Length: 4\r\n\r\nbo"[..],
+        );
+
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(b"dy");
+
+        let message = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(message.method(), Some("GET"));
+        assert_eq!(message.header("X-Test"), Some("a, b"));
+        assert_eq!(&message.body[..], b"body");
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn header_limits() {
+        let cfg = HttpCodecCfg {
+            max_headers: 2,
+            ..Default::default()
+        };
+        let mut codec = HttpCodec::new(Span::none(), HttpMsg::Response, cfg);
+        assert!(codec.decode(&mut BytesMut::from(RESPONSE)).is_err());
+
+        let cfg = HttpCodecCfg {
+            max_header_size: 16,
+            ..Default::default()
+        };
+        let mut codec = HttpCodec::new(Span::none(), HttpMsg::Response, cfg);
+        assert!(codec.decode(&mut BytesMut::from(&RESPONSE[..20])).is_err());
+    }
+
+    #[test]
+    fn body_limits() {
+        let response = |content_length: &str| {
+            let mut response = BytesMut::from(&b"HTTP/1.1 503 Service Unavailable\r\n"[..]);
+            response
+                .extend_from_slice(format!("Content-Length: {content_length}\r\n\r\n").as_bytes());
+            response
+        };
+
+        let cfg = HttpCodecCfg {
+            max_body_size: 16,
+            ..Default::default()
+        };
+        let mut codec = HttpCodec::new(Span::none(), HttpMsg::Response, cfg);
+        assert!(codec.decode(&mut response("16")).unwrap().is_none());
+        assert!(codec.decode(&mut response("17")).is_err());
+
+        // Huge declared sizes are rejected without overflowing or reserving any memory.
+        let mut codec = HttpCodec::new(Span::none(), HttpMsg::Response, Default::default());
+        for content_length in [usize::MAX, usize::MAX - 1, DEFAULT_MAX_BODY_SIZE + 1] {
+            let mut src = response(&content_length.to_string());
+            assert!(codec.decode(&mut src).is_err());
+            assert!(src.capacity() < DEFAULT_MAX_BODY_SIZE);
+        }
+
+        let cfg = HttpCodecCfg {
+            max_body_size: usize::MAX,
+            ..Default::default()
+        };
+        let mut codec = HttpCodec::new(Span::none(), HttpMsg::Response, cfg);
+        assert!(codec
+            .decode(&mut response(&usize::MAX.to_string()))
+            .is_err());
     }
 }
@@ -1,11 +1,23 @@
 use std::io;
 
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use prost::Message;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
 use tokio_util::codec::{Decoder, Encoder};
 use tracing::*;
 
-use crate::protocol::proto::*;
+use crate::{
+    protocol::{
+        proto::*,
+        render::{render_unknown, Render},
+    },
+    tools::{
+        capture::{ConnectionCapture, Direction},
+        stats::PeerStats,
+    },
+};
 
 const HEADER_LEN_COMPRESSED: u32 = 10;
 
@@ -21,228 +33,656 @@ const COMPRESSED_FALSE: u8 = 0xfc;
 
 const PROTOCOL_ERROR: u8 = 0x0c;
 
-#[derive(Debug)]
-enum Compression {
+// Messages with a payload of this size or smaller are never compressed
+// (see `Message::compress` in ripple/overlay/impl/Message.cpp).
+const COMPRESSION_MIN_PAYLOAD_SIZE: usize = 70;
+
+/// The maximum size of a message rippled accepts
+/// (see `maximiumMessageSize` in ripple/overlay/Message.h).
+pub const MAX_MESSAGE_SIZE: u32 = 64 * 1024 * 1024;
+
+/// The compression algorithm applied to a message's payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
     None,
     LZ4,
 }
 
-#[derive(Debug)]
+/// A message header, as seen on the wire.
+#[derive(Debug, Clone)]
 pub struct Header {
-    #[allow(dead_code)]
     total_wire_size: u32,
-    #[allow(dead_code)]
     header_size: u32,
     payload_wire_size: u32,
-    #[allow(dead_code)]
     uncompressed_size: u32,
     message_type: u16,
-    #[allow(dead_code)]
     compression: Compression,
 }
 
-#[derive(Debug, Clone)]
-#[non_exhaustive]
-pub enum Payload {
-    TmManifests(TmManifests),
-    TmPing(TmPing),
-    TmCluster(TmCluster),
-    TmEndpoints(TmEndpoints),
-    TmTransaction(TmTransaction),
-    TmGetLedger(TmGetLedger),
-    TmLedgerData(TmLedgerData),
-    TmProposeLedger(TmProposeSet),
-    TmStatusChange(TmStatusChange),
-    TmHaveTransactions(TmHaveTransactions),
-    TmHaveSet(TmHaveTransactionSet),
-    TmValidation(TmValidation),
-    TmGetObjectByHash(TmGetObjectByHash),
-    TmValidatorList(TmValidatorList),
-    TmSquelch(TmSquelch),
-    TmValidatorListCollection(TmValidatorListCollection),
-    TmProofPathRequest(TmProofPathRequest),
-    TmProofPathResponse(TmProofPathResponse),
-    TmReplayDeltaRequest(TmReplayDeltaRequest),
-    TmReplayDeltaResponse(TmReplayDeltaResponse),
-    TmGetPeerShardInfoV2(TmGetPeerShardInfoV2),
-    TmPeerShardInfoV2(TmPeerShardInfoV2),
-    TmTransactions(TmTransactions),
+impl Header {
+    /// The size of the whole frame (header and payload) on the wire.
+    pub fn total_wire_size(&self) -> u32 {
+        self.total_wire_size
+    }
+
+    /// The size of the header on the wire; 10 bytes if compressed, 6 bytes otherwise.
+    pub fn header_size(&self) -> u32 {
+        self.header_size
+    }
+
+    /// The size of the (possibly compressed) payload on the wire.
+    pub fn payload_wire_size(&self) -> u32 {
+        self.payload_wire_size
+    }
+
+    /// The size of the payload after decompression, as declared by the header.
+    pub fn uncompressed_size(&self) -> u32 {
+        self.uncompressed_size
+    }
+
+    pub fn message_type(&self) -> u16 {
+        self.message_type
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compression != Compression::None
+    }
+}
+
+// Generates `Payload` along with its mapping to and from the wire message types, and its name and
+// rendering (see `render`).
+//
+// Every variant of the proto `MessageType` enum has to be listed either as a handled message
+// or as an excluded one (decoded as `Payload::Unknown`), otherwise `Payload::decode_from`
+// fails to compile due to a non-exhaustive match.
+macro_rules! message_registry {
+    (
+        handled: { $($variant:ident($msg:ty) = $message_type:ident,)* }
+        excluded: { $($excluded:ident,)* }
+    ) => {
+        #[derive(Debug, Clone, PartialEq)]
+        #[non_exhaustive]
+        pub enum Payload {
+            $($variant($msg),)*
+            /// A message of a valid type which isn't modelled (yet), together with its raw payload.
+            Unknown {
+                message_type: u16,
+                raw: Bytes,
+            },
+        }
+
+        impl Payload {
+            /// Returns the name of the payload's message, e.g. `TmPing`.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant(_) => stringify!($variant),)*
+                    Self::Unknown { .. } => "Unknown",
+                }
+            }
+
+            /// Returns the wire message type of the payload.
+            pub fn message_type(&self) -> u16 {
+                match self {
+                    $(Self::$variant(_) => MessageType::$message_type as u16,)*
+                    Self::Unknown { message_type, .. } => *message_type,
+                }
+            }
+
+            /// Decodes a payload of the given wire message type.
+            pub fn decode_from<B: Buf>(
+                message_type: u16,
+                mut bytes: B,
+            ) -> Result<Self, DecodeError> {
+                let known_type = MessageType::from_i32(message_type as i32)
+                    .ok_or(DecodeError::UnknownMessageType(message_type))?;
+
+                let protobuf_err = |source| DecodeError::Protobuf {
+                    message_type,
+                    source,
+                };
+
+                let payload = match known_type {
+                    $(MessageType::$message_type => {
+                        Self::$variant(Message::decode(&mut bytes).map_err(protobuf_err)?)
+                    })*
+                    $(MessageType::$excluded)|* => Self::Unknown {
+                        message_type,
+                        raw: bytes.copy_to_bytes(bytes.remaining()),
+                    },
+                };
+
+                Ok(payload)
+            }
+
+            /// Returns the length of the encoded payload (without the header).
+            pub fn encoded_len(&self) -> usize {
+                match self {
+                    $(Self::$variant(msg) => msg.encoded_len(),)*
+                    Self::Unknown { raw, .. } => raw.len(),
+                }
+            }
+
+            /// Encodes the payload (without the header) into the given buffer.
+            pub fn encode_to(&self, dst: &mut BytesMut) {
+                match self {
+                    // Encoding into a `BytesMut` can't fail, as it grows as needed.
+                    $(Self::$variant(msg) => msg.encode(dst).unwrap(),)*
+                    Self::Unknown { raw, .. } => dst.put(&raw[..]),
+                }
+            }
+        }
+
+        /// The payload is rendered as an object with a single key, the name of the message.
+        impl Render for Payload {
+            fn render(&self) -> Value {
+                let rendered = match self {
+                    $(Self::$variant(msg) => msg.render(),)*
+                    Self::Unknown { message_type, raw } => render_unknown(*message_type, raw),
+                };
+
+                json!({ self.name(): rendered })
+            }
+        }
+    };
+}
+
+message_registry! {
+    handled: {
+        TmManifests(TmManifests) = MtManifests,
+        TmPing(TmPing) = MtPing,
+        TmCluster(TmCluster) = MtCluster,
+        TmEndpoints(TmEndpoints) = MtEndpoints,
+        TmTransaction(TmTransaction) = MtTransaction,
+        TmGetLedger(TmGetLedger) = MtGetLedger,
+        TmLedgerData(TmLedgerData) = MtLedgerData,
+        TmProposeLedger(TmProposeSet) = MtProposeLedger,
+        TmStatusChange(TmStatusChange) = MtStatusChange,
+        TmHaveTransactions(TmHaveTransactions) = MtHaveTransactions,
+        TmHaveSet(TmHaveTransactionSet) = MtHaveSet,
+        TmValidation(TmValidation) = MtValidation,
+        TmGetObjectByHash(TmGetObjectByHash) = MtGetObjects,
+        TmValidatorList(TmValidatorList) = MtValidatorlist,
+        TmSquelch(TmSquelch) = MtSquelch,
+        TmValidatorListCollection(TmValidatorListCollection) = MtValidatorlistcollection,
+        TmProofPathRequest(TmProofPathRequest) = MtProofPathReq,
+        TmProofPathResponse(TmProofPathResponse) = MtProofPathResponse,
+        TmReplayDeltaRequest(TmReplayDeltaRequest) = MtReplayDeltaReq,
+        TmReplayDeltaResponse(TmReplayDeltaResponse) = MtReplayDeltaResponse,
+        TmGetPeerShardInfoV2(TmGetPeerShardInfoV2) = MtGetPeerShardInfoV2,
+        TmPeerShardInfoV2(TmPeerShardInfoV2) = MtPeerShardInfoV2,
+        TmTransactions(TmTransactions) = MtTransactions,
+    }
+    // The legacy shard messages, superseded by their V2 counterparts.
+    excluded: {
+        MtGetShardInfo,
+        MtShardInfo,
+        MtGetPeerShardInfo,
+        MtPeerShardInfo,
+    }
 }
 
 #[derive(Debug)]
 pub struct BinaryMessage {
     pub header: Header,
     pub payload: Payload,
+    /// The raw frame (header and payload) exactly as received; only kept if
+    /// [`MessageCodecCfg::keep_raw_frames`] is enabled.
+    pub raw_frame: Option<Bytes>,
+}
+
+impl BinaryMessage {
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn payload(&self) -> &Payload {
+        &self.payload
+    }
+
+    pub fn raw_frame(&self) -> Option<&Bytes> {
+        self.raw_frame.as_ref()
+    }
+}
+
+/// A message whose payload is only decoded on demand.
+#[derive(Debug, Clone)]
+pub struct RawBinaryMessage {
+    pub header: Header,
+    /// The payload as it was received, i.e. still compressed if the header says so.
+    pub payload: Bytes,
+    /// The raw frame (header and payload) exactly as received; only kept if
+    /// [`MessageCodecCfg::keep_raw_frames`] is enabled.
+    pub raw_frame: Option<Bytes>,
+}
+
+impl RawBinaryMessage {
+    /// Decompresses (if needed) and decodes the payload.
+    pub fn decode(&self) -> Result<Payload, DecodeError> {
+        let payload = match self.header.compression {
+            Compression::None => self.payload.clone(),
+            Compression::LZ4 => {
+                decompress(&self.payload, self.header.uncompressed_size as usize)?.freeze()
+            }
+        };
+
+        Payload::decode_from(self.header.message_type, payload)
+    }
+
+    /// Decodes the payload, turning this into a [`BinaryMessage`].
+    pub fn into_binary_message(self) -> Result<BinaryMessage, DecodeError> {
+        let payload = self.decode()?;
+
+        Ok(BinaryMessage {
+            header: self.header,
+            payload,
+            raw_frame: self.raw_frame,
+        })
+    }
+}
+
+/// Errors which can occur while decoding a message.
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error("bad header: {0}")]
+    BadHeader(String),
+
+    #[error("unsupported compression algorithm: {0:#04x}")]
+    UnsupportedCompression(u8),
+
+    #[error("LZ4 decompression failed: {0}")]
+    Decompression(String),
+
+    #[error("unknown message type: {0}")]
+    UnknownMessageType(u16),
+
+    #[error("frame of type {message_type} declares {size} bytes, exceeding the limit of {max}")]
+    FrameTooLarge {
+        message_type: u16,
+        size: u32,
+        max: u32,
+    },
+
+    #[error("unable to decode a message of type {message_type}: {source}")]
+    Protobuf {
+        message_type: u16,
+        source: prost::DecodeError,
+    },
+}
+
+impl From<DecodeError> for io::Error {
+    fn from(e: DecodeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// What to do with frames exceeding [`MessageCodecCfg::max_frame_size`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizedFramePolicy {
+    /// Fail with [`DecodeError::FrameTooLarge`], which disconnects the peer.
+    #[default]
+    Disconnect,
+    /// Discard the frame as it arrives, without buffering it, log a warning and count it in the
+    /// connection's [`skipped_frames`](crate::tools::stats::ConnectionStats::skipped_frames).
+    Skip,
+}
+
+/// Message codec configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct MessageCodecCfg {
+    /// Whether to LZ4-compress outbound messages, even if compression wasn't negotiated during
+    /// the handshake (see [`ProtocolFeatures`](crate::protocol::features::ProtocolFeatures)).
+    ///
+    /// Only the message types rippled compresses are considered and only when compression
+    /// actually saves space. Compressed inbound messages are always accepted.
+    pub compression: bool,
+    /// Whether to keep the raw bytes of every inbound frame next to the decoded message.
+    pub keep_raw_frames: bool,
+    /// The maximum payload size an inbound frame may declare, both on the wire and once
+    /// decompressed. Defaults to [`MAX_MESSAGE_SIZE`].
+    pub max_frame_size: u32,
+    /// The handling of inbound frames exceeding `max_frame_size`.
+    pub oversized_frame_policy: OversizedFramePolicy,
+    /// Whether to hand out inbound messages as [`RawBinaryMessage`]s, decoding them only on
+    /// demand; useful for high-throughput scenarios where most messages are of no interest.
+    pub lazy_decoding: bool,
+}
+
+impl Default for MessageCodecCfg {
+    fn default() -> Self {
+        Self {
+            compression: false,
+            keep_raw_frames: false,
+            max_frame_size: MAX_MESSAGE_SIZE,
+            oversized_frame_policy: Default::default(),
+            lazy_decoding: false,
+        }
+    }
 }
 
 pub struct MessageCodec {
     current_msg_header: Option<Header>,
+    // The number of bytes of a skipped oversized frame which are yet to be discarded.
+    bytes_to_skip: usize,
+    // The codec configuration for the associated connection.
+    cfg: MessageCodecCfg,
+    // The wire capture of the associated connection, if enabled.
+    capture: Option<ConnectionCapture>,
+    // The message statistics of the associated connection, if enabled.
+    stats: Option<PeerStats>,
     // The associated node's span.
     span: Span,
 }
 
 impl MessageCodec {
-    pub fn new(span: Span) -> Self {
+    pub fn new(span: Span, cfg: MessageCodecCfg) -> Self {
         Self {
             current_msg_header: None,
+            bytes_to_skip: 0,
+            cfg,
+            capture: None,
+            stats: None,
             span,
         }
     }
-}
 
-impl Decoder for MessageCodec {
-    type Item = BinaryMessage;
-    type Error = io::Error;
+    /// Records every frame sent and received with this codec.
+    pub fn with_capture(mut self, capture: ConnectionCapture) -> Self {
+        self.capture = Some(capture);
+        self
+    }
 
-    // Based on Ripple's `invokeProtocolMessage` (ripple/overlay/impl/ProtocolMessage.cpp)
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        if src.is_empty() {
-            return Ok(None);
+    /// Keeps the statistics of every frame sent and received with this codec.
+    pub fn with_stats(mut self, stats: PeerStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    pub fn cfg(&self) -> &MessageCodecCfg {
+        &self.cfg
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Records the frame in the wire capture and the statistics, if enabled.
+    pub(crate) fn record(&self, direction: Direction, message_type: Option<u16>, frame: &[u8]) {
+        if let Some(ref capture) = self.capture {
+            if let Err(e) = capture.record(direction, message_type, frame) {
+                error!(parent: &self.span, "unable to record a frame: {e}");
+            }
+        }
+        if let Some(ref stats) = self.stats {
+            stats.record_frame(direction, message_type, frame.len());
         }
+    }
 
-        if self.current_msg_header.is_none() {
-            if src[0] & COMPRESSED_TRUE != 0 {
-                trace!(parent: &self.span, "processing a compressed message");
+    /// Logs the decode error and records it in the statistics, if enabled.
+    pub(crate) fn record_decode_error(&self, error: &DecodeError) {
+        error!(parent: &self.span, "unable to decode a message: {error}");
+        if let Some(ref stats) = self.stats {
+            stats.record_decode_error();
+        }
+    }
+}
 
-                let header_size = HEADER_LEN_COMPRESSED;
-                if src.remaining() < header_size as usize {
+impl MessageCodec {
+    /// Decodes a single message, returning a typed error in case of failure.
+    ///
+    /// Returns `Ok(None)` if there isn't enough data buffered yet.
+    pub fn decode_message(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<BinaryMessage>, DecodeError> {
+        self.decode_raw_message(src)?
+            .map(RawBinaryMessage::into_binary_message)
+            .transpose()
+    }
+
+    /// Splits off a single message frame, leaving its payload undecoded.
+    ///
+    /// Returns `Ok(None)` if there isn't enough data buffered yet.
+    // Based on Ripple's `invokeProtocolMessage` (ripple/overlay/impl/ProtocolMessage.cpp)
+    pub fn decode_raw_message(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<RawBinaryMessage>, DecodeError> {
+        // Skipped frames are followed by another iteration, as a single read can contain any
+        // number of them.
+        loop {
+            if self.bytes_to_skip != 0 {
+                let skipped = self.bytes_to_skip.min(src.len());
+                src.advance(skipped);
+                self.bytes_to_skip -= skipped;
+
+                if self.bytes_to_skip != 0 {
                     return Ok(None);
                 }
+            }
 
-                // protocol error
-                if (src[0] & PROTOCOL_ERROR) != 0 {
-                    unimplemented!();
-                }
+            if src.is_empty() {
+                return Ok(None);
+            }
 
-                let header_bytes = src.split_to(header_size as usize);
-                let mut iter = header_bytes.into_iter();
+            if self.current_msg_header.is_none() {
+                if src[0] & COMPRESSED_TRUE != 0 {
+                    trace!(parent: &self.span, "processing a compressed message");
 
-                let compression = src[0] & COMPRESSION_ALGO;
-                trace!(parent: &self.span, "compression: {:x}", compression);
+                    let header_size = HEADER_LEN_COMPRESSED;
+                    if src.remaining() < header_size as usize {
+                        return Ok(None);
+                    }
 
-                // only LZ4 is currently supported
-                if compression != COMPRESSION_LZ4 {
-                    unimplemented!();
-                }
+                    if (src[0] & PROTOCOL_ERROR) != 0 {
+                        return Err(DecodeError::BadHeader(format!(
+                            "protocol error bits set: {:#04x}",
+                            src[0]
+                        )));
+                    }
 
-                let mut payload_wire_size = 0;
-                for _ in 0..4 {
-                    payload_wire_size = (payload_wire_size << 8u32) + iter.next().unwrap() as u32;
-                }
-                payload_wire_size &= 0x0FFFFFFF; // clear the top four bits (the compression bits)
+                    let compression = src[0] & COMPRESSION_ALGO;
+                    trace!(parent: &self.span, "compression: {:x}", compression);
 
-                let total_wire_size = header_size + payload_wire_size;
+                    // only LZ4 is currently supported
+                    if compression != COMPRESSION_LZ4 {
+                        return Err(DecodeError::UnsupportedCompression(compression));
+                    }
 
-                let mut message_type = 0;
-                for _ in 0..2 {
-                    message_type = (message_type << 8u16) + iter.next().unwrap() as u16;
-                }
+                    // The header is only consumed together with the payload, so that the whole
+                    // frame can be kept if needed.
+                    let mut iter = src[..header_size as usize].iter().copied();
 
-                let mut uncompressed_size = 0;
-                for _ in 0..4 {
-                    uncompressed_size = (uncompressed_size << 8u32) + iter.next().unwrap() as u32;
-                }
+                    let mut payload_wire_size = 0;
+                    for _ in 0..4 {
+                        payload_wire_size =
+                            (payload_wire_size << 8u32) + iter.next().unwrap() as u32;
+                    }
+                    payload_wire_size &= 0x0FFFFFFF; // clear the top four bits (the compression bits)
 
-                let header = Header {
-                    total_wire_size,
-                    header_size,
-                    payload_wire_size,
-                    uncompressed_size,
-                    message_type,
-                    compression: Compression::LZ4,
-                };
+                    let total_wire_size = header_size + payload_wire_size;
 
-                self.current_msg_header = Some(header);
-            } else if src[0] & COMPRESSED_FALSE == 0 {
-                trace!(parent: &self.span, "processing an uncompressed message");
+                    let mut message_type = 0;
+                    for _ in 0..2 {
+                        message_type = (message_type << 8u16) + iter.next().unwrap() as u16;
+                    }
 
-                let header_size = HEADER_LEN_UNCOMPRESSED;
-                if src.remaining() < header_size as usize {
-                    return Ok(None);
-                }
+                    let mut uncompressed_size = 0;
+                    for _ in 0..4 {
+                        uncompressed_size =
+                            (uncompressed_size << 8u32) + iter.next().unwrap() as u32;
+                    }
 
-                let header_bytes = src.split_to(header_size as usize);
-                let mut iter = header_bytes.into_iter();
+                    let header = Header {
+                        total_wire_size,
+                        header_size,
+                        payload_wire_size,
+                        uncompressed_size,
+                        message_type,
+                        compression: Compression::LZ4,
+                    };
+                    trace!(parent: &self.span, "header: {:?}", header);
+                    self.current_msg_header = Some(header);
+                } else if src[0] & COMPRESSED_FALSE == 0 {
+                    trace!(parent: &self.span, "processing an uncompressed message");
 
-                let mut payload_wire_size = 0;
-                for _ in 0..4 {
-                    payload_wire_size = (payload_wire_size << 8u32) + iter.next().unwrap() as u32;
-                }
+                    let header_size = HEADER_LEN_UNCOMPRESSED;
+                    if src.remaining() < header_size as usize {
+                        return Ok(None);
+                    }
+
+                    let mut iter = src[..header_size as usize].iter().copied();
+
+                    let mut payload_wire_size = 0;
+                    for _ in 0..4 {
+                        payload_wire_size =
+                            (payload_wire_size << 8u32) + iter.next().unwrap() as u32;
+                    }
 
-                let uncompressed_size = payload_wire_size;
-                let total_wire_size = header_size + payload_wire_size;
+                    let uncompressed_size = payload_wire_size;
+                    let total_wire_size = header_size + payload_wire_size;
 
-                let mut message_type = 0;
-                for _ in 0..2 {
-                    message_type = (message_type << 8u16) + iter.next().unwrap() as u16;
+                    let mut message_type = 0;
+                    for _ in 0..2 {
+                        message_type = (message_type << 8u16) + iter.next().unwrap() as u16;
+                    }
+
+                    let header = Header {
+                        total_wire_size,
+                        header_size,
+                        payload_wire_size,
+                        uncompressed_size,
+                        message_type,
+                        compression: Compression::None,
+                    };
+                    trace!(parent: &self.span, "header: {:?}", header);
+                    self.current_msg_header = Some(header);
+                } else {
+                    return Err(DecodeError::BadHeader(format!(
+                        "invalid compression indicator: {:#04x}",
+                        src[0]
+                    )));
                 }
 
-                let header = Header {
-                    total_wire_size,
-                    header_size,
-                    payload_wire_size,
-                    uncompressed_size,
-                    message_type,
-                    compression: Compression::None,
-                };
-                trace!(parent: &self.span, "header: {:?}", header);
-                self.current_msg_header = Some(header);
-            } else {
-                error!(parent: &self.span, "invalid compression indicator");
+                let header = self.current_msg_header.as_ref().unwrap();
+                let size = header.payload_wire_size.max(header.uncompressed_size);
+                if size > self.cfg.max_frame_size {
+                    let header = self.current_msg_header.take().unwrap();
+
+                    match self.cfg.oversized_frame_policy {
+                        OversizedFramePolicy::Disconnect => {
+                            return Err(DecodeError::FrameTooLarge {
+                                message_type: header.message_type,
+                                size,
+                                max: self.cfg.max_frame_size,
+                            });
+                        }
+                        OversizedFramePolicy::Skip => {
+                            warn!(
+                                parent: &self.span,
+                                "skipping an oversized frame ({size}B, the limit is {}B): {:?}",
+                                self.cfg.max_frame_size,
+                                header
+                            );
+
+                            if let Some(ref stats) = self.stats {
+                                stats.record_skipped_frame();
+                            }
+
+                            src.advance(header.header_size as usize);
+                            self.bytes_to_skip = header.payload_wire_size as usize;
 
-                return Err(io::ErrorKind::InvalidData.into());
+                            // Carry on with whatever follows the skipped frame.
+                            continue;
+                        }
+                    }
+                }
             }
-        }
 
-        if let Some(Header {
-            payload_wire_size, ..
-        }) = self.current_msg_header
-        {
-            if src.remaining() < payload_wire_size as usize {
+            let total_wire_size = match self.current_msg_header {
+                Some(ref header) => header.total_wire_size,
+                None => unreachable!(),
+            };
+
+            if src.remaining() < total_wire_size as usize {
                 return Ok(None);
             }
 
             let header = self.current_msg_header.take().unwrap();
-            let mut payload = src.split_to(payload_wire_size as usize);
-
-            let payload = match header.message_type {
-                2 => Payload::TmManifests(Message::decode(&mut payload)?),
-                3 => Payload::TmPing(Message::decode(&mut payload)?),
-                5 => Payload::TmCluster(Message::decode(&mut payload)?),
-                15 => Payload::TmEndpoints(Message::decode(&mut payload)?),
-                30 => Payload::TmTransaction(Message::decode(&mut payload)?),
-                31 => Payload::TmGetLedger(Message::decode(&mut payload)?),
-                32 => Payload::TmLedgerData(Message::decode(&mut payload)?),
-                33 => Payload::TmProposeLedger(Message::decode(&mut payload)?),
-                34 => Payload::TmStatusChange(Message::decode(&mut payload)?),
-                35 => Payload::TmHaveSet(Message::decode(&mut payload)?),
-                41 => Payload::TmValidation(Message::decode(&mut payload)?),
-                42 => Payload::TmGetObjectByHash(Message::decode(&mut payload)?),
-                54 => Payload::TmValidatorList(Message::decode(&mut payload)?),
-                55 => Payload::TmSquelch(Message::decode(&mut payload)?),
-                56 => Payload::TmValidatorListCollection(Message::decode(&mut payload)?),
-                57 => Payload::TmProofPathRequest(Message::decode(&mut payload)?),
-                58 => Payload::TmProofPathResponse(Message::decode(&mut payload)?),
-                59 => Payload::TmReplayDeltaRequest(Message::decode(&mut payload)?),
-                60 => Payload::TmReplayDeltaResponse(Message::decode(&mut payload)?),
-                61 => Payload::TmGetPeerShardInfoV2(Message::decode(&mut payload)?),
-                62 => Payload::TmPeerShardInfoV2(Message::decode(&mut payload)?),
-                63 => Payload::TmHaveTransactions(Message::decode(&mut payload)?),
-                64 => Payload::TmTransactions(Message::decode(&mut payload)?),
-                _ => unimplemented!(),
+            let mut frame = src.split_to(total_wire_size as usize);
+            self.record(Direction::Inbound, Some(header.message_type), &frame);
+
+            let (payload, raw_frame) = if self.cfg.keep_raw_frames {
+                let frame = frame.freeze();
+                (frame.slice(header.header_size as usize..), Some(frame))
+            } else {
+                (frame.split_off(header.header_size as usize).freeze(), None)
             };
 
-            let message = BinaryMessage { header, payload };
+            let message = RawBinaryMessage {
+                header,
+                payload,
+                raw_frame,
+            };
 
             debug!(parent: &self.span, "decoded a header: {:?}", message.header);
 
-            Ok(Some(message))
-        } else {
-            unreachable!();
+            return Ok(Some(message));
         }
     }
 }
 
+impl Decoder for MessageCodec {
+    type Item = BinaryMessage;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        self.decode_message(src).map_err(|e| {
+            self.record_decode_error(&e);
+            e.into()
+        })
+    }
+}
+
+// Decompresses an LZ4 block, making sure it matches the size declared in the header.
+fn decompress(payload: &[u8], uncompressed_size: usize) -> Result<BytesMut, DecodeError> {
+    let decompressed = lz4_flex::block::decompress(payload, uncompressed_size)
+        .map_err(|e| DecodeError::Decompression(e.to_string()))?;
+
+    if decompressed.len() != uncompressed_size {
+        return Err(DecodeError::Decompression(format!(
+            "decompressed {} bytes, but the header declared {uncompressed_size}",
+            decompressed.len()
+        )));
+    }
+
+    Ok(BytesMut::from(&decompressed[..]))
+}
+
+// Returns true for message types which rippled compresses
+// (see `Message::compress` in ripple/overlay/impl/Message.cpp).
+fn is_compressible(message_type: u16) -> bool {
+    [
+        MessageType::MtManifests,
+        MessageType::MtEndpoints,
+        MessageType::MtTransaction,
+        MessageType::MtGetLedger,
+        MessageType::MtLedgerData,
+        MessageType::MtGetObjects,
+        MessageType::MtValidatorlist,
+        MessageType::MtValidatorlistcollection,
+        MessageType::MtReplayDeltaResponse,
+        MessageType::MtTransactions,
+    ]
+    .iter()
+    .any(|compressible| *compressible as u16 == message_type)
+}
+
 // Based on `pack` from Ripple's `Message::setHeader` (ripple/overlay/impl/Message.cpp)
 fn pack(dst: &mut [u8], size: u32) {
     dst[0] = ((size >> 24) & 0x0f) as u8;
@@ -251,132 +691,75 @@ fn pack(dst: &mut [u8], size: u32) {
     dst[3] = (size & 0xff) as u8;
 }
 
+// Based on Ripple's `Message::setHeader` (ripple/overlay/impl/Message.cpp)
+fn put_header(dst: &mut BytesMut, header: &Header) {
+    let mut header_bytes = [0u8; HEADER_LEN_COMPRESSED as usize];
+
+    pack(&mut header_bytes, header.payload_wire_size);
+
+    header_bytes[4] = ((header.message_type >> 8) & 0xff) as u8;
+    header_bytes[5] = (header.message_type & 0xff) as u8;
+
+    if header.compression == Compression::LZ4 {
+        pack(&mut header_bytes[6..], header.uncompressed_size);
+        header_bytes[0] |= COMPRESSION_LZ4;
+    }
+
+    dst.put(&header_bytes[..header.header_size as usize]);
+}
+
 impl Encoder<Payload> for MessageCodec {
     type Error = io::Error;
 
-    // Based on Ripple's `Message::Message` (ripple/overlay/impl/Message.cpp)
+    // Based on Ripple's `Message::Message` and `Message::compress` (ripple/overlay/impl/Message.cpp)
     fn encode(&mut self, message: Payload, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let (payload_len, msg_type) = match &message {
-            Payload::TmManifests(msg) => {
-                (msg.encoded_len() as u32, MessageType::MtManifests as i32)
-            }
-            Payload::TmPing(msg) => (msg.encoded_len() as u32, MessageType::MtPing as i32),
-            Payload::TmCluster(msg) => (msg.encoded_len() as u32, MessageType::MtCluster as i32),
-            Payload::TmEndpoints(msg) => {
-                (msg.encoded_len() as u32, MessageType::MtEndpoints as i32)
-            }
-            Payload::TmTransaction(msg) => {
-                (msg.encoded_len() as u32, MessageType::MtTransaction as i32)
-            }
-            Payload::TmGetLedger(msg) => {
-                (msg.encoded_len() as u32, MessageType::MtGetLedger as i32)
-            }
-            Payload::TmLedgerData(msg) => {
-                (msg.encoded_len() as u32, MessageType::MtLedgerData as i32)
-            }
-            Payload::TmProposeLedger(msg) => (
-                msg.encoded_len() as u32,
-                MessageType::MtProposeLedger as i32,
-            ),
-            Payload::TmStatusChange(msg) => {
-                (msg.encoded_len() as u32, MessageType::MtStatusChange as i32)
-            }
-            Payload::TmValidation(msg) => {
-                (msg.encoded_len() as u32, MessageType::MtValidation as i32)
-            }
-            Payload::TmGetObjectByHash(msg) => {
-                (msg.encoded_len() as u32, MessageType::MtGetObjects as i32)
-            }
-            Payload::TmValidatorList(msg) => (
-                msg.encoded_len() as u32,
-                MessageType::MtValidatorlist as i32,
-            ),
-            Payload::TmSquelch(msg) => (msg.encoded_len() as u32, MessageType::MtSquelch as i32),
-            Payload::TmHaveSet(msg) => (msg.encoded_len() as u32, MessageType::MtHaveSet as i32),
-            Payload::TmValidatorListCollection(msg) => (
-                msg.encoded_len() as u32,
-                MessageType::MtValidatorlistcollection as i32,
-            ),
-            Payload::TmProofPathRequest(msg) => {
-                (msg.encoded_len() as u32, MessageType::MtProofPathReq as i32)
-            }
-            Payload::TmProofPathResponse(msg) => (
-                msg.encoded_len() as u32,
-                MessageType::MtProofPathResponse as i32,
-            ),
-            Payload::TmReplayDeltaRequest(msg) => (
-                msg.encoded_len() as u32,
-                MessageType::MtReplayDeltaReq as i32,
-            ),
-            Payload::TmReplayDeltaResponse(msg) => (
-                msg.encoded_len() as u32,
-                MessageType::MtReplayDeltaResponse as i32,
-            ),
-            Payload::TmGetPeerShardInfoV2(msg) => (
-                msg.encoded_len() as u32,
-                MessageType::MtGetPeerShardInfoV2 as i32,
-            ),
-            Payload::TmPeerShardInfoV2(msg) => (
-                msg.encoded_len() as u32,
-                MessageType::MtPeerShardInfoV2 as i32,
-            ),
-            Payload::TmHaveTransactions(msg) => (
-                msg.encoded_len() as u32,
-                MessageType::MtHaveTransactions as i32,
-            ),
-            Payload::TmTransactions(msg) => {
-                (msg.encoded_len() as u32, MessageType::MtTransactions as i32)
+        let frame_start = dst.len();
+        let message_type = message.message_type();
+        let mut bytes = BytesMut::with_capacity(message.encoded_len());
+        message.encode_to(&mut bytes);
+        let payload_len = bytes.len() as u32;
+
+        if self.cfg.compression
+            && is_compressible(message_type)
+            && bytes.len() > COMPRESSION_MIN_PAYLOAD_SIZE
+        {
+            let compressed = lz4_flex::block::compress(&bytes);
+
+            // Compression has to make up for the larger header, otherwise it's not worth it.
+            let header_overhead = (HEADER_LEN_COMPRESSED - HEADER_LEN_UNCOMPRESSED) as usize;
+            if compressed.len() < bytes.len() - header_overhead {
+                let header_size = HEADER_LEN_COMPRESSED;
+                let header = Header {
+                    total_wire_size: header_size + compressed.len() as u32,
+                    header_size,
+                    payload_wire_size: compressed.len() as u32,
+                    uncompressed_size: payload_len,
+                    message_type,
+                    compression: Compression::LZ4,
+                };
+                trace!(parent: &self.span, "compressed header: {:?}", header);
+
+                put_header(dst, &header);
+                dst.put(&compressed[..]);
+                self.record(Direction::Outbound, Some(message_type), &dst[frame_start..]);
+
+                return Ok(());
             }
-        };
+        }
 
         let header_size = HEADER_LEN_UNCOMPRESSED;
-        let _header = Header {
+        let header = Header {
             total_wire_size: header_size + payload_len,
             header_size,
             payload_wire_size: payload_len,
             uncompressed_size: payload_len,
-            message_type: msg_type as u16,
-            compression: Compression::None, // TODO: are compressed messages used?
-        };
-
-        let mut header_bytes = [0u8; HEADER_LEN_UNCOMPRESSED as usize];
-
-        pack(&mut header_bytes, payload_len);
-
-        header_bytes[4] = ((msg_type >> 8) & 0xff) as u8;
-        header_bytes[5] = (msg_type & 0xff) as u8;
-
-        let mut bytes = BytesMut::new();
-
-        bytes.put(&header_bytes[..]);
-
-        match message {
-            Payload::TmManifests(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmPing(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmCluster(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmEndpoints(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmTransaction(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmGetLedger(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmLedgerData(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmProposeLedger(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmStatusChange(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmValidation(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmGetObjectByHash(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmValidatorList(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmSquelch(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmHaveSet(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmValidatorListCollection(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmProofPathResponse(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmProofPathRequest(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmReplayDeltaRequest(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmReplayDeltaResponse(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmGetPeerShardInfoV2(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmPeerShardInfoV2(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmTransactions(msg) => (msg.encode(&mut bytes).unwrap(),),
-            Payload::TmHaveTransactions(msg) => (msg.encode(&mut bytes).unwrap(),),
+            message_type,
+            compression: Compression::None,
         };
 
+        put_header(dst, &header);
         dst.put(&*bytes);
+        self.record(Direction::Outbound, Some(message_type), &dst[frame_start..]);
 
         Ok(())
     }
@@ -385,6 +768,7 @@ impl Encoder<Payload> for MessageCodec {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tools::stats::Stats;
 
     #[test]
     fn decode_and_encode() {
@@ -400,7 +784,7 @@ mod tests {
             \xad\"~\xb2\xdd\xb93\xf7V\xa1Zc\xe2D\xf8\x8bf\xd3"[..],
         );
 
-        let mut codec = MessageCodec::new(Span::none());
+        let mut codec = MessageCodec::new(Span::none(), Default::default());
         let msg = codec.decode(&mut raw.clone()).unwrap().unwrap();
 
         let mut encoded = BytesMut::new();
@@ -408,4 +792,301 @@ mod tests {
 
         assert_eq!(raw, encoded);
     }
+
+    #[test]
+    fn compressed_encode_and_decode() {
+        // a highly compressible message of a type which rippled compresses
+        let payload = Payload::TmLedgerData(TmLedgerData {
+            ledger_hash: vec![0xab; 32],
+            ledger_seq: 7,
+            r#type: TmLedgerInfoType::LiAsNode as i32,
+            nodes: vec![
+                TmLedgerNode {
+                    nodedata: vec![0x42; 512],
+                    nodeid: Some(vec![0x11; 33]),
+                };
+                16
+            ],
+            request_cookie: None,
+            error: None,
+        });
+
+        let cfg = MessageCodecCfg {
+            compression: true,
+            ..Default::default()
+        };
+        let mut codec = MessageCodec::new(Span::none(), cfg);
+        let mut encoded = BytesMut::new();
+        codec.encode(payload.clone(), &mut encoded).unwrap();
+
+        assert_eq!(encoded[0] & COMPRESSION_ALGO, COMPRESSION_LZ4);
+
+        let msg = codec.decode(&mut encoded.clone()).unwrap().unwrap();
+        assert_eq!(msg.header.compression, Compression::LZ4);
+        assert_eq!(msg.header.payload_wire_size as usize + 10, encoded.len());
+        assert_eq!(msg.payload, payload);
+
+        // the same message is sent uncompressed when compression is disabled
+        let mut codec = MessageCodec::new(Span::none(), Default::default());
+        let mut uncompressed = BytesMut::new();
+        codec.encode(payload.clone(), &mut uncompressed).unwrap();
+
+        assert!(uncompressed.len() > encoded.len());
+        assert_eq!(uncompressed[0] & COMPRESSED_TRUE, 0);
+        assert_eq!(
+            msg.header.uncompressed_size as usize + 6,
+            uncompressed.len()
+        );
+    }
+
+    #[test]
+    fn small_messages_are_not_compressed() {
+        let payload = Payload::TmEndpoints(TmEndpoints {
+            version: 2,
+            endpoints_v2: vec![],
+        });
+
+        let cfg = MessageCodecCfg {
+            compression: true,
+            ..Default::default()
+        };
+        let mut codec = MessageCodec::new(Span::none(), cfg);
+        let mut encoded = BytesMut::new();
+        codec.encode(payload, &mut encoded).unwrap();
+
+        assert_eq!(encoded[0] & COMPRESSED_TRUE, 0);
+        assert_eq!(encoded.len(), HEADER_LEN_UNCOMPRESSED as usize + 2);
+    }
+
+    #[test]
+    fn unmodelled_message_type_is_decoded_as_unknown() {
+        // mtGET_PEER_SHARD_INFO (52) is a valid message type which isn't modelled
+        let raw = BytesMut::from(&b"\0\0\0\x03\0\x34abc"[..]);
+
+        let mut codec = MessageCodec::new(Span::none(), Default::default());
+        let msg = codec.decode(&mut raw.clone()).unwrap().unwrap();
+        assert_eq!(
+            msg.payload,
+            Payload::Unknown {
+                message_type: 52,
+                raw: Bytes::from_static(b"abc"),
+            }
+        );
+
+        let mut encoded = BytesMut::new();
+        codec.encode(msg.payload, &mut encoded).unwrap();
+        assert_eq!(raw, encoded);
+    }
+
+    #[test]
+    fn invalid_messages_return_decode_errors() {
+        let mut codec = MessageCodec::new(Span::none(), Default::default());
+
+        // a message type which doesn't exist
+        let mut raw = BytesMut::from(&b"\0\0\0\0\xff\xff"[..]);
+        assert!(matches!(
+            codec.decode_message(&mut raw),
+            Err(DecodeError::UnknownMessageType(0xffff))
+        ));
+
+        // the protocol error bits are set
+        let mut raw = BytesMut::from(&b"\x9c\0\0\0\0\x03\0\0\0\0"[..]);
+        assert!(matches!(
+            codec.decode_message(&mut raw),
+            Err(DecodeError::BadHeader(_))
+        ));
+
+        // an unsupported compression algorithm
+        let mut raw = BytesMut::from(&b"\xa0\0\0\0\0\x03\0\0\0\0"[..]);
+        assert!(matches!(
+            codec.decode_message(&mut raw),
+            Err(DecodeError::UnsupportedCompression(0xa0))
+        ));
+
+        // a payload which isn't a valid TmPing
+        let mut raw = BytesMut::from(&b"\0\0\0\x02\0\x03\xff\xff"[..]);
+        assert!(matches!(
+            codec.decode_message(&mut raw),
+            Err(DecodeError::Protobuf {
+                message_type: 3,
+                ..
+            })
+        ));
+
+        // the decoder surfaces all of the above as fatal `InvalidData` errors
+        let mut raw = BytesMut::from(&b"\0\0\0\0\xff\xff"[..]);
+        let err = codec.decode(&mut raw).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn registry_covers_all_message_types() {
+        for message_type in 0..=u16::MAX {
+            let result = Payload::decode_from(message_type, Bytes::new());
+
+            if MessageType::from_i32(message_type as i32).is_some() {
+                let payload = result.unwrap();
+                assert_eq!(payload.message_type(), message_type);
+
+                let mut encoded = BytesMut::new();
+                payload.encode_to(&mut encoded);
+                assert_eq!(encoded.len(), payload.encoded_len());
+            } else {
+                assert!(matches!(result, Err(DecodeError::UnknownMessageType(_))));
+            }
+        }
+    }
+
+    #[test]
+    fn raw_frames_are_kept_when_enabled() {
+        let payload = Payload::TmLedgerData(TmLedgerData {
+            ledger_hash: vec![0xab; 32],
+            ledger_seq: 7,
+            r#type: TmLedgerInfoType::LiAsNode as i32,
+            nodes: vec![
+                TmLedgerNode {
+                    nodedata: vec![0x42; 512],
+                    nodeid: None,
+                };
+                4
+            ],
+            request_cookie: None,
+            error: None,
+        });
+
+        let cfg = MessageCodecCfg {
+            compression: true,
+            keep_raw_frames: true,
+            ..Default::default()
+        };
+        let mut codec = MessageCodec::new(Span::none(), cfg);
+        let mut encoded = BytesMut::new();
+        codec.encode(payload.clone(), &mut encoded).unwrap();
+
+        // the frame is decoded correctly even if it arrives in two parts
+        let mut src = BytesMut::from(&encoded[..5]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(&encoded[5..]);
+        let msg = codec.decode(&mut src).unwrap().unwrap();
+        assert!(src.is_empty());
+
+        let header = msg.header();
+        assert!(header.is_compressed());
+        assert_eq!(header.compression(), Compression::LZ4);
+        assert_eq!(header.message_type(), MessageType::MtLedgerData as u16);
+        assert_eq!(header.header_size(), HEADER_LEN_COMPRESSED);
+        assert_eq!(header.total_wire_size() as usize, encoded.len());
+        assert_eq!(
+            header.payload_wire_size(),
+            header.total_wire_size() - header.header_size()
+        );
+        assert_eq!(header.uncompressed_size() as usize, payload.encoded_len());
+        assert_eq!(msg.raw_frame().unwrap()[..], encoded[..]);
+        assert_eq!(msg.payload(), &payload);
+
+        // raw frames aren't kept by default
+        let mut codec = MessageCodec::new(Span::none(), Default::default());
+        let msg = codec.decode(&mut encoded).unwrap().unwrap();
+        assert!(msg.raw_frame().is_none());
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected_or_skipped() {
+        let ping = Payload::TmPing(TmPing {
+            r#type: tm_ping::PingType::PtPing as i32,
+            seq: Some(1),
+            ping_time: None,
+            net_time: None,
+        });
+        let mut ping_frame = BytesMut::new();
+        MessageCodec::new(Span::none(), Default::default())
+            .encode(ping.clone(), &mut ping_frame)
+            .unwrap();
+
+        // an uncompressed TmPing frame declaring a 1KiB payload
+        let mut oversized = BytesMut::from(&b"\0\0\x04\0\0\x03"[..]);
+        oversized.extend_from_slice(&[0u8; 1024]);
+
+        // by default, the limit is enforced by failing
+        let cfg = MessageCodecCfg {
+            max_frame_size: 512,
+            ..Default::default()
+        };
+        let mut codec = MessageCodec::new(Span::none(), cfg.clone());
+        assert!(matches!(
+            codec.decode_message(&mut oversized.clone()),
+            Err(DecodeError::FrameTooLarge {
+                message_type: 3,
+                size: 1024,
+                max: 512
+            })
+        ));
+
+        // a compressed frame declaring an oversized uncompressed payload is rejected early
+        let mut compressed = BytesMut::from(&b"\x90\0\0\x10\0\x03\0\x01\0\0"[..]);
+        assert!(matches!(
+            codec.decode_message(&mut compressed),
+            Err(DecodeError::FrameTooLarge { size: 65536, .. })
+        ));
+
+        // alternatively, the frame is skipped as it arrives and decoding carries on afterwards
+        let cfg = MessageCodecCfg {
+            oversized_frame_policy: OversizedFramePolicy::Skip,
+            ..cfg
+        };
+        let mut codec = MessageCodec::new(Span::none(), cfg);
+
+        let mut src = BytesMut::from(&oversized[..100]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        assert!(src.is_empty());
+
+        src.extend_from_slice(&oversized[100..]);
+        src.extend_from_slice(&ping_frame);
+        let msg = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(msg.payload, ping);
+        assert!(src.is_empty());
+
+        // any number of skipped frames can be buffered at once and they are counted in the stats
+        let stats = Stats::default();
+        let peer = "127.0.0.1:51235".parse().unwrap();
+        let mut codec = codec.with_stats(stats.for_peer(peer));
+
+        const SKIPPED: usize = 100_000;
+        let mut src = BytesMut::new();
+        for _ in 0..SKIPPED {
+            src.extend_from_slice(b"\x90\0\0\0\0\x03\0\x01\0\0");
+        }
+        src.extend_from_slice(&ping_frame);
+        let msg = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(msg.payload, ping);
+        assert_eq!(stats.get(peer).unwrap().skipped_frames, SKIPPED as u64);
+    }
+
+    #[test]
+    fn raw_messages_are_decoded_on_demand() {
+        let payload = Payload::TmValidation(TmValidation {
+            validation: vec![0x07; 256],
+            ..Default::default()
+        });
+
+        let cfg = MessageCodecCfg {
+            compression: true,
+            ..Default::default()
+        };
+        let mut codec = MessageCodec::new(Span::none(), cfg);
+        let mut encoded = BytesMut::new();
+        codec.encode(payload.clone(), &mut encoded).unwrap();
+        codec.encode(payload.clone(), &mut encoded).unwrap();
+
+        let raw = codec.decode_raw_message(&mut encoded).unwrap().unwrap();
+        assert_eq!(raw.header.message_type(), MessageType::MtValidation as u16);
+        assert_eq!(raw.payload.len() as u32, raw.header.payload_wire_size());
+        assert_eq!(raw.decode().unwrap(), payload);
+        assert_eq!(raw.into_binary_message().unwrap().payload, payload);
+
+        // raw and decoded messages can be interleaved
+        let msg = codec.decode_message(&mut encoded).unwrap().unwrap();
+        assert_eq!(msg.payload, payload);
+        assert!(encoded.is_empty());
+    }
 }
@@ -1,2 +1,5 @@
 pub mod http;
 pub mod message;
+
+#[cfg(test)]
+mod proptests;
@@ -0,0 +1,489 @@
+//! Property-based tests for the message codec.
+
+#![allow(deprecated)] // some of the proto fields are deprecated, but still valid on the wire
+
+use bytes::{Bytes, BytesMut};
+use proptest::{collection::vec, option, prelude::*};
+use tokio_util::codec::{Decoder, Encoder};
+use tracing::Span;
+
+use crate::protocol::{
+    codecs::message::{MessageCodec, MessageCodecCfg, Payload},
+    proto::{tm_endpoints::TmEndpointv2, tm_peer_shard_info_v2::TmIncomplete, *},
+};
+
+// Keeps the generated messages small enough for the tests to stay fast.
+const MAX_COLLECTION_LEN: usize = 8;
+
+fn bytes() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..64)
+}
+
+fn hash() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 32)
+}
+
+fn string() -> impl Strategy<Value = String> {
+    "\\PC{0,32}"
+}
+
+prop_compose! {
+    fn tm_manifests()(
+        list in vec(bytes().prop_map(|stobject| TmManifest { stobject }), 0..MAX_COLLECTION_LEN),
+        history in option::of(any::<bool>()),
+    ) -> TmManifests {
+        TmManifests { list, history }
+    }
+}
+
+prop_compose! {
+    fn tm_ping()(
+        r#type in any::<i32>(),
+        seq in option::of(any::<u32>()),
+        ping_time in option::of(any::<u64>()),
+        net_time in option::of(any::<u64>()),
+    ) -> TmPing {
+        TmPing { r#type, seq, ping_time, net_time }
+    }
+}
+
+prop_compose! {
+    fn tm_cluster_node()(
+        public_key in string(),
+        report_time in any::<u32>(),
+        node_load in any::<u32>(),
+        node_name in option::of(string()),
+        address in option::of(string()),
+    ) -> TmClusterNode {
+        TmClusterNode { public_key, report_time, node_load, node_name, address }
+    }
+}
+
+prop_compose! {
+    fn tm_load_source()(
+        name in string(),
+        cost in any::<u32>(),
+        count in option::of(any::<u32>()),
+    ) -> TmLoadSource {
+        TmLoadSource { name, cost, count }
+    }
+}
+
+prop_compose! {
+    fn tm_cluster()(
+        cluster_nodes in vec(tm_cluster_node(), 0..MAX_COLLECTION_LEN),
+        load_sources in vec(tm_load_source(), 0..MAX_COLLECTION_LEN),
+    ) -> TmCluster {
+        TmCluster { cluster_nodes, load_sources }
+    }
+}
+
+prop_compose! {
+    fn tm_endpoints()(
+        version in any::<u32>(),
+        endpoints_v2 in vec(
+            (string(), any::<u32>()).prop_map(|(endpoint, hops)| TmEndpointv2 { endpoint, hops }),
+            0..MAX_COLLECTION_LEN,
+        ),
+    ) -> TmEndpoints {
+        TmEndpoints { version, endpoints_v2 }
+    }
+}
+
+prop_compose! {
+    fn tm_transaction()(
+        raw_transaction in bytes(),
+        status in any::<i32>(),
+        receive_timestamp in option::of(any::<u64>()),
+        deferred in option::of(any::<bool>()),
+    ) -> TmTransaction {
+        TmTransaction { raw_transaction, status, receive_timestamp, deferred }
+    }
+}
+
+prop_compose! {
+    fn tm_get_ledger()(
+        itype in any::<i32>(),
+        ltype in option::of(any::<i32>()),
+        ledger_hash in option::of(hash()),
+        ledger_seq in option::of(any::<u32>()),
+        node_i_ds in vec(bytes(), 0..MAX_COLLECTION_LEN),
+        request_cookie in option::of(any::<u64>()),
+        query_type in option::of(any::<i32>()),
+        query_depth in option::of(any::<u32>()),
+    ) -> TmGetLedger {
+        TmGetLedger {
+            itype,
+            ltype,
+            ledger_hash,
+            ledger_seq,
+            node_i_ds,
+            request_cookie,
+            query_type,
+            query_depth,
+        }
+    }
+}
+
+prop_compose! {
+    fn tm_ledger_node()(nodedata in bytes(), nodeid in option::of(bytes())) -> TmLedgerNode {
+        TmLedgerNode { nodedata, nodeid }
+    }
+}
+
+prop_compose! {
+    fn tm_ledger_data()(
+        ledger_hash in hash(),
+        ledger_seq in any::<u32>(),
+        r#type in any::<i32>(),
+        nodes in vec(tm_ledger_node(), 0..MAX_COLLECTION_LEN),
+        request_cookie in option::of(any::<u32>()),
+        error in option::of(any::<i32>()),
+    ) -> TmLedgerData {
+        TmLedgerData { ledger_hash, ledger_seq, r#type, nodes, request_cookie, error }
+    }
+}
+
+prop_compose! {
+    fn tm_propose_set()(
+        propose_seq in any::<u32>(),
+        current_tx_hash in hash(),
+        node_pub_key in bytes(),
+        close_time in any::<u32>(),
+        signature in bytes(),
+        previousledger in hash(),
+        added_transactions in vec(hash(), 0..MAX_COLLECTION_LEN),
+        removed_transactions in vec(hash(), 0..MAX_COLLECTION_LEN),
+        checked_signature in option::of(any::<bool>()),
+        hops in option::of(any::<u32>()),
+    ) -> TmProposeSet {
+        TmProposeSet {
+            propose_seq,
+            current_tx_hash,
+            node_pub_key,
+            close_time,
+            signature,
+            previousledger,
+            added_transactions,
+            removed_transactions,
+            checked_signature,
+            hops,
+        }
+    }
+}
+
+prop_compose! {
+    fn tm_status_change()(
+        new_status in option::of(any::<i32>()),
+        new_event in option::of(any::<i32>()),
+        ledger_seq in option::of(any::<u32>()),
+        ledger_hash in option::of(hash()),
+        ledger_hash_previous in option::of(hash()),
+        network_time in option::of(any::<u64>()),
+        first_seq in option::of(any::<u32>()),
+        last_seq in option::of(any::<u32>()),
+    ) -> TmStatusChange {
+        TmStatusChange {
+            new_status,
+            new_event,
+            ledger_seq,
+            ledger_hash,
+            ledger_hash_previous,
+            network_time,
+            first_seq,
+            last_seq,
+        }
+    }
+}
+
+prop_compose! {
+    fn tm_have_transaction_set()(status in any::<i32>(), hash in hash()) -> TmHaveTransactionSet {
+        TmHaveTransactionSet { status, hash }
+    }
+}
+
+prop_compose! {
+    fn tm_validation()(
+        validation in bytes(),
+        checked_signature in option::of(any::<bool>()),
+        hops in option::of(any::<u32>()),
+    ) -> TmValidation {
+        TmValidation { validation, checked_signature, hops }
+    }
+}
+
+prop_compose! {
+    fn tm_indexed_object()(
+        hash in option::of(hash()),
+        node_id in option::of(bytes()),
+        index in option::of(bytes()),
+        data in option::of(bytes()),
+        ledger_seq in option::of(any::<u32>()),
+    ) -> TmIndexedObject {
+        TmIndexedObject { hash, node_id, index, data, ledger_seq }
+    }
+}
+
+prop_compose! {
+    fn tm_get_object_by_hash()(
+        r#type in any::<i32>(),
+        query in any::<bool>(),
+        seq in option::of(any::<u32>()),
+        ledger_hash in option::of(hash()),
+        fat in option::of(any::<bool>()),
+        objects in vec(tm_indexed_object(), 0..MAX_COLLECTION_LEN),
+    ) -> TmGetObjectByHash {
+        TmGetObjectByHash { r#type, query, seq, ledger_hash, fat, objects }
+    }
+}
+
+prop_compose! {
+    fn tm_validator_list()(
+        manifest in bytes(),
+        blob in bytes(),
+        signature in bytes(),
+        version in any::<u32>(),
+    ) -> TmValidatorList {
+        TmValidatorList { manifest, blob, signature, version }
+    }
+}
+
+prop_compose! {
+    fn tm_squelch()(
+        squelch in any::<bool>(),
+        validator_pub_key in bytes(),
+        squelch_duration in option::of(any::<u32>()),
+    ) -> TmSquelch {
+        TmSquelch { squelch, validator_pub_key, squelch_duration }
+    }
+}
+
+prop_compose! {
+    fn validator_blob_info()(
+        manifest in option::of(bytes()),
+        blob in bytes(),
+        signature in bytes(),
+    ) -> ValidatorBlobInfo {
+        ValidatorBlobInfo { manifest, blob, signature }
+    }
+}
+
+prop_compose! {
+    fn tm_validator_list_collection()(
+        version in any::<u32>(),
+        manifest in bytes(),
+        blobs in vec(validator_blob_info(), 0..MAX_COLLECTION_LEN),
+    ) -> TmValidatorListCollection {
+        TmValidatorListCollection { version, manifest, blobs }
+    }
+}
+
+prop_compose! {
+    fn tm_proof_path_request()(
+        key in hash(),
+        ledger_hash in hash(),
+        r#type in any::<i32>(),
+    ) -> TmProofPathRequest {
+        TmProofPathRequest { key, ledger_hash, r#type }
+    }
+}
+
+prop_compose! {
+    fn tm_proof_path_response()(
+        key in hash(),
+        ledger_hash in hash(),
+        r#type in any::<i32>(),
+        ledger_header in option::of(bytes()),
+        path in vec(bytes(), 0..MAX_COLLECTION_LEN),
+        error in option::of(any::<i32>()),
+    ) -> TmProofPathResponse {
+        TmProofPathResponse { key, ledger_hash, r#type, ledger_header, path, error }
+    }
+}
+
+prop_compose! {
+    fn tm_replay_delta_response()(
+        ledger_hash in hash(),
+        ledger_header in option::of(bytes()),
+        transaction in vec(bytes(), 0..MAX_COLLECTION_LEN),
+        error in option::of(any::<i32>()),
+    ) -> TmReplayDeltaResponse {
+        TmReplayDeltaResponse { ledger_hash, ledger_header, transaction, error }
+    }
+}
+
+fn public_keys() -> impl Strategy<Value = Vec<TmPublicKey>> {
+    vec(
+        bytes().prop_map(|public_key| TmPublicKey { public_key }),
+        0..MAX_COLLECTION_LEN,
+    )
+}
+
+prop_compose! {
+    fn tm_get_peer_shard_info_v2()(
+        peer_chain in public_keys(),
+        relays in any::<u32>(),
+    ) -> TmGetPeerShardInfoV2 {
+        TmGetPeerShardInfoV2 { peer_chain, relays }
+    }
+}
+
+prop_compose! {
+    fn tm_incomplete()(
+        shard_index in any::<u32>(),
+        state in any::<u32>(),
+        progress in option::of(any::<u32>()),
+    ) -> TmIncomplete {
+        TmIncomplete { shard_index, state, progress }
+    }
+}
+
+prop_compose! {
+    fn tm_peer_shard_info_v2()(
+        timestamp in any::<u32>(),
+        incomplete in vec(tm_incomplete(), 0..MAX_COLLECTION_LEN),
+        finalized in option::of(string()),
+        public_key in bytes(),
+        signature in bytes(),
+        peer_chain in public_keys(),
+    ) -> TmPeerShardInfoV2 {
+        TmPeerShardInfoV2 { timestamp, incomplete, finalized, public_key, signature, peer_chain }
+    }
+}
+
+prop_compose! {
+    fn tm_have_transactions()(hashes in vec(hash(), 0..MAX_COLLECTION_LEN)) -> TmHaveTransactions {
+        TmHaveTransactions { hashes }
+    }
+}
+
+prop_compose! {
+    fn tm_transactions()(
+        transactions in vec(tm_transaction(), 0..MAX_COLLECTION_LEN),
+    ) -> TmTransactions {
+        TmTransactions { transactions }
+    }
+}
+
+prop_compose! {
+    // Only the message types which aren't modelled are decoded as `Payload::Unknown`.
+    fn unknown()(
+        message_type in prop_oneof![
+            Just(MessageType::MtGetShardInfo),
+            Just(MessageType::MtShardInfo),
+            Just(MessageType::MtGetPeerShardInfo),
+            Just(MessageType::MtPeerShardInfo),
+        ],
+        raw in bytes(),
+    ) -> Payload {
+        Payload::Unknown { message_type: message_type as u16, raw: Bytes::from(raw) }
+    }
+}
+
+fn payload() -> impl Strategy<Value = Payload> {
+    prop_oneof![
+        tm_manifests().prop_map(Payload::TmManifests),
+        tm_ping().prop_map(Payload::TmPing),
+        tm_cluster().prop_map(Payload::TmCluster),
+        tm_endpoints().prop_map(Payload::TmEndpoints),
+        tm_transaction().prop_map(Payload::TmTransaction),
+        tm_get_ledger().prop_map(Payload::TmGetLedger),
+        tm_ledger_data().prop_map(Payload::TmLedgerData),
+        tm_propose_set().prop_map(Payload::TmProposeLedger),
+        tm_status_change().prop_map(Payload::TmStatusChange),
+        tm_have_transactions().prop_map(Payload::TmHaveTransactions),
+        tm_have_transaction_set().prop_map(Payload::TmHaveSet),
+        tm_validation().prop_map(Payload::TmValidation),
+        tm_get_object_by_hash().prop_map(Payload::TmGetObjectByHash),
+        tm_validator_list().prop_map(Payload::TmValidatorList),
+        tm_squelch().prop_map(Payload::TmSquelch),
+        tm_validator_list_collection().prop_map(Payload::TmValidatorListCollection),
+        tm_proof_path_request().prop_map(Payload::TmProofPathRequest),
+        tm_proof_path_response().prop_map(Payload::TmProofPathResponse),
+        hash().prop_map(
+            |ledger_hash| Payload::TmReplayDeltaRequest(TmReplayDeltaRequest { ledger_hash })
+        ),
+        tm_replay_delta_response().prop_map(Payload::TmReplayDeltaResponse),
+        tm_get_peer_shard_info_v2().prop_map(Payload::TmGetPeerShardInfoV2),
+        tm_peer_shard_info_v2().prop_map(Payload::TmPeerShardInfoV2),
+        tm_transactions().prop_map(Payload::TmTransactions),
+        unknown(),
+    ]
+}
+
+fn codec_cfg() -> impl Strategy<Value = MessageCodecCfg> {
+    (any::<bool>(), any::<bool>()).prop_map(|(compression, keep_raw_frames)| MessageCodecCfg {
+        compression,
+        keep_raw_frames,
+        ..Default::default()
+    })
+}
+
+// Splits the given bytes into chunks of the given sizes; whatever is left forms the last chunk.
+fn split_into_chunks(mut bytes: Bytes, chunk_sizes: &[usize]) -> Vec<Bytes> {
+    let mut chunks = Vec::with_capacity(chunk_sizes.len() + 1);
+    for size in chunk_sizes {
+        let size = (*size).min(bytes.len());
+        chunks.push(bytes.split_to(size));
+    }
+    chunks.push(bytes);
+
+    chunks
+}
+
+proptest! {
+    #[test]
+    fn encode_decode_round_trip(payload in payload(), cfg in codec_cfg()) {
+        let mut codec = MessageCodec::new(Span::none(), cfg);
+
+        let mut encoded = BytesMut::new();
+        codec.encode(payload.clone(), &mut encoded).unwrap();
+        let wire_size = encoded.len();
+
+        let msg = codec.decode(&mut encoded).unwrap().unwrap();
+        prop_assert!(encoded.is_empty());
+        prop_assert_eq!(msg.header.total_wire_size() as usize, wire_size);
+        prop_assert_eq!(msg.header.message_type(), payload.message_type());
+        prop_assert_eq!(msg.header.uncompressed_size() as usize, payload.encoded_len());
+        prop_assert_eq!(msg.payload, payload);
+    }
+
+    #[test]
+    fn decode_arbitrarily_chunked_stream(
+        payloads in vec(payload(), 1..MAX_COLLECTION_LEN),
+        chunk_sizes in vec(0..256usize, 0..64),
+        cfg in codec_cfg(),
+    ) {
+        let mut codec = MessageCodec::new(Span::none(), cfg);
+
+        let mut stream = BytesMut::new();
+        for payload in &payloads {
+            codec.encode(payload.clone(), &mut stream).unwrap();
+        }
+
+        // Feed the decoder one chunk at a time, the way a socket would.
+        let mut src = BytesMut::new();
+        let mut decoded = Vec::with_capacity(payloads.len());
+        for chunk in split_into_chunks(stream.freeze(), &chunk_sizes) {
+            src.extend_from_slice(&chunk);
+
+            while let Some(msg) = codec.decode_raw_message(&mut src).unwrap() {
+                decoded.push(msg.decode().unwrap());
+            }
+        }
+
+        prop_assert!(src.is_empty());
+        prop_assert_eq!(decoded, payloads);
+    }
+
+    #[test]
+    fn decoding_arbitrary_bytes_does_not_panic(
+        bytes in vec(any::<u8>(), 0..512),
+        cfg in codec_cfg(),
+    ) {
+        let mut codec = MessageCodec::new(Span::none(), cfg);
+        let mut src = BytesMut::from(&bytes[..]);
+
+        while let Ok(Some(_)) = codec.decode(&mut src) {}
+    }
+}
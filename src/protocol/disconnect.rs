@@ -0,0 +1,16 @@
+use std::net::SocketAddr;
+
+use pea2pea::protocols::Disconnect;
+
+use crate::tools::inner_node::InnerNode;
+
+#[async_trait::async_trait]
+impl Disconnect for InnerNode {
+    async fn handle_disconnect(&self, addr: SocketAddr) {
+        self.clear_peer_state(addr);
+
+        if let Some(ref stats) = self.stats {
+            stats.disconnect(addr);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! The optional protocol features negotiated with the `X-Protocol-Ctl` handshake header.
+//!
+//! The header lists `feature=value` pairs separated by `;`, e.g. `compr=lz4;txrr=1`; the
+//! responder answers with the features both peers support (see `makeFeaturesRequestHeader` and
+//! `makeFeaturesResponseHeader` in ripple/overlay/impl/Handshake.cpp).
+
+use std::fmt;
+
+use serde::Deserialize;
+
+const FEATURE_COMPR: &str = "compr";
+const FEATURE_VPRR: &str = "vprr";
+const FEATURE_TXRR: &str = "txrr";
+const FEATURE_LEDGER_REPLAY: &str = "ledgerreplay";
+
+const DELIM_FEATURE: char = ';';
+const DELIM_VALUE: char = ',';
+
+/// A set of the optional protocol features.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ProtocolFeatures {
+    /// LZ4 compression of the messages (`compr=lz4`).
+    pub compression: bool,
+    /// Reduce-relay of the validations and proposals (`vprr=1`).
+    pub vp_reduce_relay: bool,
+    /// Reduce-relay of the transactions (`txrr=1`).
+    pub tx_reduce_relay: bool,
+    /// Ledger replay (`ledgerreplay=1`).
+    pub ledger_replay: bool,
+}
+
+impl ProtocolFeatures {
+    /// Parses the value of the `X-Protocol-Ctl` header; unknown features are ignored.
+    pub fn parse(header: &str) -> Self {
+        let mut features = Self::default();
+
+        for (name, values) in header
+            .split(DELIM_FEATURE)
+            .filter_map(|feature| feature.split_once('='))
+        {
+            // Like rippled, accept a feature if the expected value is one of the listed ones.
+            let has_value = |expected: &str| {
+                values
+                    .split(DELIM_VALUE)
+                    .any(|value| value.trim().eq_ignore_ascii_case(expected))
+            };
+
+            match name.trim() {
+                FEATURE_COMPR => features.compression = has_value("lz4"),
+                FEATURE_VPRR => features.vp_reduce_relay = has_value("1"),
+                FEATURE_TXRR => features.tx_reduce_relay = has_value("1"),
+                FEATURE_LEDGER_REPLAY => features.ledger_replay = has_value("1"),
+                _ => {}
+            }
+        }
+
+        features
+    }
+
+    /// Returns the features enabled in both sets.
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self {
+            compression: self.compression && other.compression,
+            vp_reduce_relay: self.vp_reduce_relay && other.vp_reduce_relay,
+            tx_reduce_relay: self.tx_reduce_relay && other.tx_reduce_relay,
+            ledger_replay: self.ledger_replay && other.ledger_replay,
+        }
+    }
+}
+
+/// Formats the features as the value of the `X-Protocol-Ctl` header.
+impl fmt::Display for ProtocolFeatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let features = [
+            (self.compression, FEATURE_COMPR, "lz4"),
+            (self.vp_reduce_relay, FEATURE_VPRR, "1"),
+            (self.tx_reduce_relay, FEATURE_TXRR, "1"),
+            (self.ledger_replay, FEATURE_LEDGER_REPLAY, "1"),
+        ];
+
+        let mut first = true;
+        for (_, name, value) in features.iter().filter(|(enabled, ..)| *enabled) {
+            if !first {
+                write!(f, "{DELIM_FEATURE}")?;
+            }
+            write!(f, "{name}={value}")?;
+            first = false;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        let features = ProtocolFeatures::parse("ledgerreplay=1;txrr=1;compr=lz4;");
+        assert_eq!(
+            features,
+            ProtocolFeatures {
+                compression: true,
+                vp_reduce_relay: false,
+                tx_reduce_relay: true,
+                ledger_replay: true,
+            }
+        );
+        assert_eq!(features.to_string(), "compr=lz4;txrr=1;ledgerreplay=1");
+        assert_eq!(ProtocolFeatures::parse(&features.to_string()), features);
+
+        // Unknown features and values are ignored.
+        let features = ProtocolFeatures::parse("compr=zstd,LZ4;vprr=0;txrr=yes;unknown=1");
+        assert_eq!(
+            features,
+            ProtocolFeatures {
+                compression: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(ProtocolFeatures::parse(""), ProtocolFeatures::default());
+        assert_eq!(ProtocolFeatures::default().to_string(), "");
+    }
+
+    #[test]
+    fn intersection() {
+        let requested = ProtocolFeatures::parse("compr=lz4;vprr=1;txrr=1");
+        let supported = ProtocolFeatures::parse("txrr=1;ledgerreplay=1;vprr=1");
+
+        assert_eq!(
+            requested.intersection(&supported).to_string(),
+            "vprr=1;txrr=1"
+        );
+    }
+}
@@ -34,42 +34,88 @@
 //! > \r\n"
 //! ---------------------
 
-use std::{io, pin::Pin};
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    time::Duration,
+};
 
 use base64::{engine::general_purpose::STANDARD, Engine};
 use bytes::Bytes;
-use futures_util::{sink::SinkExt, TryStreamExt};
+use ed25519_dalek::Verifier;
+use futures_util::TryStreamExt;
 use openssl::ssl::Ssl;
 use pea2pea::{protocols::Handshake, Connection, ConnectionSide, Pea2Pea};
 use rand::{thread_rng, Rng};
+use secp256k1::{ecdsa::Signature, Message, PublicKey};
+use serde::Deserialize;
 use sha2::{Digest, Sha512};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    time::{sleep, timeout},
+};
 use tokio_openssl::SslStream;
 use tokio_util::codec::Framed;
 use tracing::*;
 
 use crate::{
-    protocol::codecs::http::{HttpCodec, HttpMsg},
-    tools::inner_node::{Crypto, InnerNode},
+    protocol::{
+        codecs::http::{HttpCodec, HttpCodecCfg, HttpHandshakeMessage, HttpMsg},
+        features::ProtocolFeatures,
+    },
+    tools::{
+        inner_node::{Crypto, InnerNode, ED25519_KEY_PREFIX},
+        trickle::TrickleStream,
+    },
 };
 
+// The default ending of the handshake message lines.
+const LINE_ENDING: &str = "\r\n";
+
 // Default handshake header values.
 const CONNECTION: &str = "Upgrade";
 const UPGRADE_REQ: &str = "XRPL/2.0, XRPL/2.1, XRPL/2.2";
 const UPGRADE_RSP: &str = "XRPL/2.2";
 const CONNECT_AS: &str = "Peer";
-// txrr - enables transaction relay
-// ledgerreplay - enables ledger replay
-const X_PROTOCOL_CTL: &str = "txrr=1;ledgerreplay=1";
 
+// The maximum duration of the handshake (pea2pea's default).
+const HANDSHAKE_TIMEOUT_MS: u64 = 3_000;
+
+// The maximum duration of a slow handshake (see `Trickle`); it leaves the peer enough time to give
+// up on it first.
+const SLOW_HANDSHAKE_TIMEOUT_MS: u64 = 60_000;
+
+// The status of a response accepting the handshake.
+const SWITCHING_PROTOCOLS: u16 = 101;
+
+// The name of the header used for the protocol feature negotiation.
+const X_PROTOCOL_CTL: &str = "X-Protocol-Ctl";
+
+// The names of the headers verified or collected into the `PeerInfo`.
+const PUBLIC_KEY: &str = "Public-Key";
+const SESSION_SIGNATURE: &str = "Session-Signature";
+const NETWORK_ID: &str = "Network-ID";
+const NETWORK_TIME: &str = "Network-Time";
+const CLOSED_LEDGER: &str = "Closed-Ledger";
+const PREVIOUS_LEDGER: &str = "Previous-Ledger";
+const INSTANCE_COOKIE: &str = "Instance-Cookie";
+const LOCAL_IP: &str = "Local-IP";
+const REMOTE_IP: &str = "Remote-IP";
+
+// The base58 token types of the node keys.
 #[repr(u8)]
-enum NodeType {
+pub(crate) enum NodeType {
     Public = 28,
-    #[allow(dead_code)]
     Private = 32,
 }
 
 /// Handshake configuration allows some customization of the handshake procedure.
-#[derive(Clone)]
+///
+/// It can be loaded from TOML, where the unset fields keep their default values.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
 pub struct HandshakeCfg {
     /// Will flip a random bit in a random byte of shared value used for session signing.
     pub bitflip_shared_val: bool,
@@ -95,15 +141,37 @@ pub struct HandshakeCfg {
     /// A handshake field for the connector name.
     pub http_connect_as: String,
 
-    /// A handshake field for the protocol CTL.
-    pub http_x_protocol_ctl: String,
+    /// The protocol features offered in the protocol CTL field; the features agreed with the
+    /// peer are enabled on the connection (see [`ProtocolFeatures`]).
+    pub protocol_features: ProtocolFeatures,
+
+    /// A handshake field for the protocol CTL; if set, it's sent instead of the
+    /// `protocol_features`, which are still used for the negotiation.
+    pub http_x_protocol_ctl: Option<String>,
 
     /// A handshake field which tells us whether the node is crawlable.
+    ///
+    /// Unless it's `public`, the node presents itself as a private peer, whose address isn't
+    /// shared in the node's `/crawl` response.
     pub http_crawl: Option<String>,
 
+    /// A handshake field for the network identifier; rippled rejects peers from other networks.
+    pub http_network_id: Option<String>,
+
     /// A handshake field for the network time.
     pub http_network_time: Option<String>,
 
+    /// A handshake field identifying the node instance; a peer presenting the same cookie is
+    /// treated as a connection to self and rejected.
+    pub http_instance_cookie: Option<u64>,
+
+    /// A handshake field for the sender's own public address.
+    pub http_local_ip: Option<IpAddr>,
+
+    /// A handshake field for the address the sender sees the peer connecting from; rippled uses
+    /// it to check its own public address.
+    pub http_remote_ip: Option<IpAddr>,
+
     /// A handshake field which contains a hash for the closed ledger.
     pub http_closed_ledger: Option<String>,
 
@@ -112,6 +180,21 @@ pub struct HandshakeCfg {
 
     /// A random field for testing HTTP headers.
     pub http_unexpected_extra_field_and_value: Option<String>,
+
+    /// If set, the handshake message is made of these lines, in order, instead of the ones
+    /// generated from the other fields (see [`HandshakeCfg::request_lines`] and
+    /// [`HandshakeCfg::response_lines`]).
+    pub http_lines: Option<Vec<HandshakeLine>>,
+
+    /// The ending of every line of the handshake message.
+    pub http_line_ending: String,
+
+    /// The limits applied when parsing the peer's handshake message.
+    pub http_codec: HttpCodecCfg,
+
+    /// If set, the handshake is sent slowly, keeping the peer waiting; it may then take up to a
+    /// minute instead of the usual 3 seconds.
+    pub trickle: Option<Trickle>,
 }
 
 impl Default for HandshakeCfg {
@@ -127,22 +210,257 @@ impl Default for HandshakeCfg {
             http_upgrade_req: UPGRADE_REQ.to_owned(),
             http_upgrade_rsp: UPGRADE_RSP.to_owned(),
             http_connect_as: CONNECT_AS.to_owned(),
-            http_x_protocol_ctl: X_PROTOCOL_CTL.to_owned(),
+            protocol_features: ProtocolFeatures {
+                tx_reduce_relay: true,
+                ledger_replay: true,
+                ..Default::default()
+            },
 
             // Optional handshake HTTP fields.
+            http_x_protocol_ctl: None,
             http_crawl: None,
+            http_network_id: None,
             http_network_time: None,
+            http_instance_cookie: None,
+            http_local_ip: None,
+            http_remote_ip: None,
             http_closed_ledger: None,
             http_prev_ledger: None,
 
             // A random field.
             http_unexpected_extra_field_and_value: None,
+
+            // The raw message options.
+            http_lines: None,
+            http_line_ending: LINE_ENDING.to_owned(),
+
+            http_codec: Default::default(),
+
+            trickle: None,
         }
     }
 }
 
+/// The information presented by a peer during the handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    /// The node public key, verified against the session signature.
+    pub public_key: Vec<u8>,
+    /// The `User-Agent` or `Server` field, depending on the connection side.
+    pub ident: Option<String>,
+    /// The protocol features agreed with the peer.
+    pub features: ProtocolFeatures,
+    /// The network identifier, sent by the nodes which aren't part of the main network.
+    pub network_id: Option<u32>,
+    /// The network time, in seconds since the Ripple epoch (2000-01-01T00:00:00Z).
+    pub network_time: Option<u64>,
+    /// The cookie identifying the peer instance.
+    pub instance_cookie: Option<u64>,
+    /// The public address the peer claims for itself.
+    pub local_ip: Option<IpAddr>,
+    /// The address the peer sees us connecting from.
+    pub remote_ip: Option<IpAddr>,
+    /// The hash of the last closed ledger.
+    pub closed_ledger: Option<[u8; 32]>,
+    /// The hash of the ledger preceding the last closed one.
+    pub previous_ledger: Option<[u8; 32]>,
+}
+
+impl PeerInfo {
+    /// Returns the base58-encoded public key, as sent in the `Public-Key` field.
+    pub fn public_key_base58(&self) -> String {
+        encode_base58(NodeType::Public, &self.public_key)
+    }
+}
+
+/// The outcome of a handshake initiated by a synthetic node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandshakeOutcome {
+    /// The peer switched to the peer protocol.
+    Accepted,
+    /// The peer declined the upgrade, e.g. with `503 Service Unavailable` if it has no free
+    /// peer slots.
+    Rejected {
+        /// The status of the response.
+        status: u16,
+        /// The alternative peers suggested by the peer.
+        redirect_peers: Vec<SocketAddr>,
+    },
+}
+
+impl HandshakeOutcome {
+    // Collects the alternative peers listed in the JSON body of the response declining the upgrade,
+    // i.e. `{"peer-ips": ["<ip>:<port>", ...]}`.
+    fn rejected(response: &HttpHandshakeMessage) -> Self {
+        #[derive(Deserialize)]
+        struct Redirect {
+            #[serde(rename = "peer-ips", default)]
+            peer_ips: Vec<String>,
+        }
+
+        let redirect_peers = serde_json::from_slice::<Redirect>(&response.body)
+            .map(|redirect| {
+                redirect
+                    .peer_ips
+                    .iter()
+                    .filter_map(|addr| addr.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self::Rejected {
+            status: response.status().unwrap_or_default(),
+            redirect_peers,
+        }
+    }
+}
+
+/// The reasons for rejecting the peer's handshake message.
+#[derive(Debug, Error)]
+pub enum VerificationError {
+    #[error("missing the {0} field")]
+    MissingHeader(&'static str),
+
+    #[error("invalid {0} field")]
+    InvalidHeader(&'static str),
+
+    #[error("the session signature doesn't match the public key")]
+    SignatureMismatch,
+
+    #[error("the peer presented our own instance cookie")]
+    SelfConnection,
+}
+
+impl From<VerificationError> for io::Error {
+    fn from(e: VerificationError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// A slow way of sending the handshake, like in a slowloris attack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trickle {
+    /// The TLS handshake messages, like the ClientHello, are sent a byte per interval.
+    Tls { interval: Duration },
+    /// The HTTP handshake message is sent a byte per interval.
+    Http { interval: Duration },
+    /// Only the given number of bytes of the HTTP handshake message is sent, and the rest never.
+    StallHttp { sent_bytes: usize },
+}
+
+/// A line of the handshake message.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HandshakeLine {
+    /// A line sent verbatim, e.g. the start line or a header.
+    Raw(String),
+    /// The `Public-Key` field with the node's (possibly bit-flipped) public key.
+    PublicKey,
+    /// The `Session-Signature` field signing the (possibly bit-flipped) shared value.
+    SessionSignature,
+}
+
+impl From<&str> for HandshakeLine {
+    fn from(line: &str) -> Self {
+        Self::Raw(line.to_owned())
+    }
+}
+
+impl HandshakeCfg {
+    /// Returns the lines of the handshake request generated from the configuration.
+    pub fn request_lines(&self) -> Vec<HandshakeLine> {
+        let mut lines = vec![
+            "GET / HTTP/1.1".into(),
+            HandshakeLine::Raw(format!("User-Agent: {}", self.http_ident)),
+            HandshakeLine::Raw(format!("Upgrade: {}", self.http_upgrade_req)),
+            HandshakeLine::Raw(format!("Connection: {}", self.http_connection)),
+            HandshakeLine::Raw(format!("Connect-As: {}", self.http_connect_as)),
+        ];
+        self.push_session_lines(&mut lines, &self.protocol_features);
+
+        lines
+    }
+
+    /// Returns the lines of the handshake response generated from the configuration, given the
+    /// features agreed with the initiator.
+    pub fn response_lines(&self, features: &ProtocolFeatures) -> Vec<HandshakeLine> {
+        let mut lines = vec![
+            "HTTP/1.1 101 Switching Protocols".into(),
+            HandshakeLine::Raw(format!("Connection: {}", self.http_connection)),
+            HandshakeLine::Raw(format!("Upgrade: {}", self.http_upgrade_rsp)),
+            HandshakeLine::Raw(format!("Connect-As: {}", self.http_connect_as)),
+            HandshakeLine::Raw(format!("Server: {}", self.http_ident)),
+        ];
+        self.push_session_lines(&mut lines, features);
+
+        lines
+    }
+
+    // Appends the fields common to the request and the response.
+    fn push_session_lines(&self, lines: &mut Vec<HandshakeLine>, features: &ProtocolFeatures) {
+        let header = |name: &str, value: &str| HandshakeLine::Raw(format!("{name}: {value}"));
+
+        if let Some(ref crawl) = self.http_crawl {
+            lines.push(header("Crawl", crawl));
+        }
+        let protocol_ctl = self
+            .http_x_protocol_ctl
+            .clone()
+            .unwrap_or_else(|| features.to_string());
+        lines.push(header(X_PROTOCOL_CTL, &protocol_ctl));
+        if let Some(ref id) = self.http_network_id {
+            lines.push(header(NETWORK_ID, id));
+        }
+        if let Some(ref time) = self.http_network_time {
+            lines.push(header(NETWORK_TIME, time));
+        }
+        lines.push(HandshakeLine::PublicKey);
+        lines.push(HandshakeLine::SessionSignature);
+        if let Some(cookie) = self.http_instance_cookie {
+            lines.push(header(INSTANCE_COOKIE, &cookie.to_string()));
+        }
+        if let Some(ip) = self.http_local_ip {
+            lines.push(header(LOCAL_IP, &ip.to_string()));
+        }
+        if let Some(ip) = self.http_remote_ip {
+            lines.push(header(REMOTE_IP, &ip.to_string()));
+        }
+        if let Some(ref ledger) = self.http_closed_ledger {
+            lines.push(header(CLOSED_LEDGER, ledger));
+        }
+        if let Some(ref ledger) = self.http_prev_ledger {
+            lines.push(header(PREVIOUS_LEDGER, ledger));
+        }
+        if let Some(ref extra) = self.http_unexpected_extra_field_and_value {
+            lines.push(HandshakeLine::Raw(extra.clone()));
+        }
+    }
+
+    // Serializes the handshake message, filling in the session fields.
+    fn serialize_lines(&self, lines: &[HandshakeLine], public_key: &str, signature: &str) -> Bytes {
+        let mut message = String::new();
+        for line in lines {
+            match line {
+                HandshakeLine::Raw(line) => message.push_str(line),
+                HandshakeLine::PublicKey => {
+                    message.push_str(&format!("{PUBLIC_KEY}: {public_key}"))
+                }
+                HandshakeLine::SessionSignature => {
+                    message.push_str(&format!("{SESSION_SIGNATURE}: {signature}"))
+                }
+            }
+            message.push_str(&self.http_line_ending);
+        }
+        // The headers end with an empty line.
+        message.push_str(&self.http_line_ending);
+
+        Bytes::from(message)
+    }
+}
+
 // Used to populate the Public-Key field.
-fn encode_base58(node_type: NodeType, public_key: &[u8]) -> String {
+pub(crate) fn encode_base58(node_type: NodeType, public_key: &[u8]) -> String {
     let mut payload = Vec::with_capacity(1 + public_key.len());
 
     payload.push(node_type as u8);
@@ -154,13 +472,153 @@ fn encode_base58(node_type: NodeType, public_key: &[u8]) -> String {
         .into_string()
 }
 
+// Used to decode the Public-Key field.
+pub(crate) fn decode_base58(node_type: NodeType, encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = bs58::decode(encoded)
+        .with_alphabet(bs58::Alphabet::RIPPLE)
+        .with_check(Some(node_type as u8))
+        .into_vec()
+        .ok()?;
+    // Remove the node type prefix.
+    bytes.remove(0);
+
+    Some(bytes)
+}
+
 // Used to populate the Session-Signature field.
 fn create_session_signature(crypto: &Crypto, shared_value: &[u8]) -> String {
-    let message = secp256k1::Message::from_slice(shared_value).unwrap();
-    let signature = crypto.engine.sign_ecdsa(&message, &crypto.private_key);
-    let serialized = signature.serialize_der();
+    STANDARD.encode(crypto.sign_digest(shared_value))
+}
+
+// Used to verify the peer's Session-Signature field.
+fn verify_session_signature(
+    crypto: &Crypto,
+    public_key: &[u8],
+    shared_value: &[u8],
+    signature: &[u8],
+) -> Result<(), VerificationError> {
+    // ed25519 keys sign the shared value as a plain message.
+    if let Some((&ED25519_KEY_PREFIX, public_key)) = public_key.split_first() {
+        let public_key = ed25519_dalek::PublicKey::from_bytes(public_key)
+            .map_err(|_| VerificationError::InvalidHeader(PUBLIC_KEY))?;
+        let signature = ed25519_dalek::Signature::try_from(signature)
+            .map_err(|_| VerificationError::InvalidHeader(SESSION_SIGNATURE))?;
+
+        return public_key
+            .verify(shared_value, &signature)
+            .map_err(|_| VerificationError::SignatureMismatch);
+    }
+
+    let public_key = PublicKey::from_slice(public_key)
+        .map_err(|_| VerificationError::InvalidHeader(PUBLIC_KEY))?;
+    let mut signature = Signature::from_der(signature)
+        .map_err(|_| VerificationError::InvalidHeader(SESSION_SIGNATURE))?;
+    // rippled doesn't require the session signature to be fully canonical, while libsecp256k1
+    // only verifies the lower-S form.
+    signature.normalize_s();
+    let message = Message::from_slice(shared_value).expect("the shared value is 32 bytes long");
+
+    crypto
+        .engine
+        .verify_ecdsa(&message, &signature, &public_key)
+        .map_err(|_| VerificationError::SignatureMismatch)
+}
+
+// Like rippled, accepts ledger hashes encoded either in hex or base64.
+fn parse_ledger_hash(value: &str) -> Option<[u8; 32]> {
+    let bytes = hex::decode(value)
+        .or_else(|_| STANDARD.decode(value))
+        .ok()?;
+
+    bytes.try_into().ok()
+}
+
+// Parses an optional field, which must be valid if present.
+fn parse_optional<T>(
+    message: &HttpHandshakeMessage,
+    name: &'static str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Option<T>, VerificationError> {
+    match message.header(name) {
+        Some(value) => parse(value.trim())
+            .map(Some)
+            .ok_or(VerificationError::InvalidHeader(name)),
+        None => Ok(None),
+    }
+}
+
+// Verifies the peer's Public-Key and Session-Signature fields against the shared value, and
+// collects the rest of the information sent in the handshake message. The peer is rejected if it
+// presents the given instance cookie of our own.
+fn verify_peer(
+    crypto: &Crypto,
+    message: &HttpHandshakeMessage,
+    shared_value: &[u8],
+    features: ProtocolFeatures,
+    own_cookie: Option<u64>,
+) -> Result<PeerInfo, VerificationError> {
+    let public_key = parse_optional(message, PUBLIC_KEY, |key| {
+        decode_base58(NodeType::Public, key)
+    })?
+    .ok_or(VerificationError::MissingHeader(PUBLIC_KEY))?;
+    let signature = parse_optional(message, SESSION_SIGNATURE, |sig| STANDARD.decode(sig).ok())?
+        .ok_or(VerificationError::MissingHeader(SESSION_SIGNATURE))?;
+    verify_session_signature(crypto, &public_key, shared_value, &signature)?;
+
+    // Like rippled, reject the cookies which can't identify an instance.
+    let instance_cookie = parse_optional(message, INSTANCE_COOKIE, |cookie| {
+        cookie.parse().ok().filter(|cookie| *cookie != 0)
+    })?;
+    if instance_cookie.is_some() && instance_cookie == own_cookie {
+        return Err(VerificationError::SelfConnection);
+    }
+
+    Ok(PeerInfo {
+        public_key,
+        ident: message
+            .header("User-Agent")
+            .or_else(|| message.header("Server"))
+            .map(str::to_owned),
+        features,
+        network_id: parse_optional(message, NETWORK_ID, |id| id.parse().ok())?,
+        network_time: parse_optional(message, NETWORK_TIME, |time| time.parse().ok())?,
+        instance_cookie,
+        local_ip: parse_optional(message, LOCAL_IP, |ip| ip.parse().ok())?,
+        remote_ip: parse_optional(message, REMOTE_IP, |ip| ip.parse().ok())?,
+        closed_ledger: parse_optional(message, CLOSED_LEDGER, parse_ledger_hash)?,
+        previous_ledger: parse_optional(message, PREVIOUS_LEDGER, parse_ledger_hash)?,
+    })
+}
 
-    STANDARD.encode(serialized)
+// Sends the HTTP handshake message, slowly if configured so.
+async fn send_message<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    message: &[u8],
+    trickle: Option<Trickle>,
+) -> io::Result<()> {
+    match trickle {
+        Some(Trickle::Http { interval }) => {
+            for (i, byte) in message.iter().enumerate() {
+                if i != 0 {
+                    sleep(interval).await;
+                }
+                stream.write_all(&[*byte]).await?;
+                stream.flush().await?;
+            }
+        }
+        Some(Trickle::StallHttp { sent_bytes }) => {
+            stream
+                .write_all(&message[..sent_bytes.min(message.len())])
+                .await?;
+            stream.flush().await?;
+        }
+        Some(Trickle::Tls { .. }) | None => {
+            stream.write_all(message).await?;
+            stream.flush().await?;
+        }
+    }
+
+    Ok(())
 }
 
 // Used as input for create_session_signature.
@@ -193,17 +651,49 @@ fn get_shared_value<S>(tls_stream: &SslStream<S>) -> io::Result<Vec<u8>> {
 
 #[async_trait::async_trait]
 impl Handshake for InnerNode {
-    async fn perform_handshake(&self, mut conn: Connection) -> io::Result<Connection> {
-        let own_conn_side = !conn.side();
-        let stream = self.take_stream(&mut conn);
-        let addr = conn.addr();
+    // The upper bound for all the handshakes; only the slow ones are allowed to take that long.
+    const TIMEOUT_MS: u64 = SLOW_HANDSHAKE_TIMEOUT_MS;
 
+    async fn perform_handshake(&self, conn: Connection) -> io::Result<Connection> {
         // The function shouldn't be called in case the handshake config is not set.
         let hs_cfg = self
             .handshake_cfg
             .as_ref()
             .expect("a handshake config is not set");
 
+        let timeout_ms = if hs_cfg.trickle.is_some() {
+            SLOW_HANDSHAKE_TIMEOUT_MS
+        } else {
+            HANDSHAKE_TIMEOUT_MS
+        };
+
+        timeout(
+            Duration::from_millis(timeout_ms),
+            self.handshake(conn, hs_cfg),
+        )
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+    }
+}
+
+impl InnerNode {
+    // Performs the TLS handshake followed by the HTTP upgrade.
+    async fn handshake(
+        &self,
+        mut conn: Connection,
+        hs_cfg: &HandshakeCfg,
+    ) -> io::Result<Connection> {
+        let own_conn_side = !conn.side();
+        let stream = self.take_stream(&mut conn);
+        let addr = conn.addr();
+
+        // the TLS handshake may be sent slowly
+        let tls_interval = match hs_cfg.trickle {
+            Some(Trickle::Tls { interval }) => Some(interval),
+            _ => None,
+        };
+        let stream = TrickleStream::new(stream, tls_interval);
+
         let tls_stream = match own_conn_side {
             ConnectionSide::Initiator => {
                 let ssl = self
@@ -219,67 +709,74 @@ impl Handshake for InnerNode {
                     error!(parent: self.node().span(), "TLS handshake error: {e}");
                     io::ErrorKind::InvalidData
                 })?;
+                tls_stream.get_mut().stop();
 
                 // get the shared value based on the TLS handshake
-                let mut shared_value = get_shared_value(&tls_stream)?;
+                let shared_value = get_shared_value(&tls_stream)?;
 
-                let public_key = &mut self.crypto.public_key.serialize().clone();
+                let mut public_key = self.crypto.public_key();
+                let mut signed_value = shared_value.clone();
                 // introduce intentional errors into handshake if needed
                 if hs_cfg.bitflip_shared_val {
-                    randomly_flip_bit(&mut shared_value);
+                    randomly_flip_bit(&mut signed_value);
                 }
                 if hs_cfg.bitflip_pub_key {
-                    randomly_flip_bit(public_key.as_mut_slice());
+                    randomly_flip_bit(&mut public_key);
                 }
 
                 // base58-encode the public key and create the session signature
-                let base58_pk = encode_base58(NodeType::Public, public_key);
-                let sig = create_session_signature(&self.crypto, &shared_value);
+                let base58_pk = encode_base58(NodeType::Public, &public_key);
+                let sig = create_session_signature(&self.crypto, &signed_value);
 
                 // prepare the HTTP request message
-                let mut req = Vec::new();
-                let mut req_header = |mut header: String| {
-                    // Append `\r\n' to every header.
-                    header.push_str("\r\n");
-                    req.extend_from_slice(header.as_bytes());
+                let lines = match hs_cfg.http_lines {
+                    Some(ref lines) => lines.clone(),
+                    None => hs_cfg.request_lines(),
                 };
-
-                req_header("GET / HTTP/1.1".into());
-                req_header(format!("User-Agent: {}", hs_cfg.http_ident));
-                req_header(format!("Upgrade: {}", hs_cfg.http_upgrade_req));
-                req_header(format!("Connection: {}", hs_cfg.http_connection));
-                req_header(format!("Connect-As: {}", hs_cfg.http_connect_as));
-                if let Some(ref crawl) = hs_cfg.http_crawl {
-                    req_header(format!("Crawl: {crawl}"))
-                };
-                req_header(format!("X-Protocol-Ctl: {}", hs_cfg.http_x_protocol_ctl));
-                if let Some(ref time) = hs_cfg.http_network_time {
-                    req_header(format!("Network-Time: {time}"))
-                };
-                req_header(format!("Public-Key: {base58_pk}"));
-                req_header(format!("Session-Signature: {sig}"));
-                if let Some(ref ledger) = hs_cfg.http_closed_ledger {
-                    req_header(format!("Closed-Ledger: {ledger}"))
-                };
-                if let Some(ref ledger) = hs_cfg.http_prev_ledger {
-                    req_header(format!("Previous-Ledger: {ledger}"))
-                };
-                if let Some(ref header) = hs_cfg.http_unexpected_extra_field_and_value {
-                    req_header(header.clone())
-                };
-                req_header("".into()); // An HTTP header ends with '\r\n'
+                let req = hs_cfg.serialize_lines(&lines, &base58_pk, &sig);
 
                 // use the HTTP codec to read/write the (post-TLS) handshake messages
-                let req = Bytes::from(req);
-                let codec = HttpCodec::new(self.node().span().clone(), HttpMsg::Response);
+                let codec = HttpCodec::new(
+                    self.node().span().clone(),
+                    HttpMsg::Response,
+                    hs_cfg.http_codec.clone(),
+                );
                 let mut framed = Framed::new(&mut tls_stream, codec);
 
                 // send the handshake HTTP request message
                 trace!(parent: self.node().span(), "sending a request to {addr}: {req:?}");
-                framed.send(req).await?;
+                send_message(framed.get_mut(), &req, hs_cfg.trickle).await?;
 
-                // read the HTTP request message (there should only be headers)
-                let _ = framed.try_next().await?.ok_or(io::ErrorKind::InvalidData)?;
+                // read the HTTP response message
+                let response = framed.try_next().await?.ok_or(io::ErrorKind::InvalidData)?;
+                trace!(parent: self.node().span(), "got a response from {addr}: {response:?}");
+
+                // the responder may decline the upgrade, e.g. if it has no free slots
+                if response.status() != Some(SWITCHING_PROTOCOLS) {
+                    let outcome = HandshakeOutcome::rejected(&response);
+                    debug!(parent: self.node().span(), "{addr} rejected the handshake: {outcome:?}");
+                    self.set_handshake_rejection(addr, outcome);
+                    return Err(io::ErrorKind::ConnectionRefused.into());
+                }
+
+                // the responder answers with the features enabled on both sides
+                let features =
+                    ProtocolFeatures::parse(response.header(X_PROTOCOL_CTL).unwrap_or_default())
+                        .intersection(&hs_cfg.protocol_features);
+
+                // the responder must have signed the same shared value
+                let peer_info = verify_peer(
+                    &self.crypto,
+                    &response,
+                    &shared_value,
+                    features,
+                    hs_cfg.http_instance_cookie,
+                )
+                .map_err(|e| {
+                    error!(parent: self.node().span(), "invalid handshake response from {addr}: {e}");
+                    e
+                })?;
+                self.set_peer_info(addr, peer_info);
 
                 tls_stream
             }
@@ -291,68 +788,68 @@ impl Handshake for InnerNode {
                     error!(parent: self.node().span(), "TLS handshake error: {e}");
                     io::ErrorKind::InvalidData
                 })?;
+                tls_stream.get_mut().stop();
 
                 // get the shared value based on the TLS handshake
-                let mut shared_value = get_shared_value(&tls_stream)?;
+                let shared_value = get_shared_value(&tls_stream)?;
 
                 // use the HTTP codec to read/write the (post-TLS) handshake messages
-                let codec = HttpCodec::new(self.node().span().clone(), HttpMsg::Request);
+                let codec = HttpCodec::new(
+                    self.node().span().clone(),
+                    HttpMsg::Request,
+                    hs_cfg.http_codec.clone(),
+                );
                 let mut framed = Framed::new(&mut tls_stream, codec);
 
                 // read the HTTP request message (there should only be headers)
-                let request_body = framed.try_next().await?.ok_or(io::ErrorKind::InvalidData)?;
-                if !request_body.is_empty() {
-                    warn!(parent: self.node().span(), "trailing bytes in the handshake request from {addr}: {request_body:?}");
+                let request = framed.try_next().await?.ok_or(io::ErrorKind::InvalidData)?;
+                trace!(parent: self.node().span(), "got a request from {addr}: {request:?}");
+                if !request.body.is_empty() || !framed.read_buffer().is_empty() {
+                    warn!(parent: self.node().span(), "trailing bytes in the handshake request from {addr}: {:?}", framed.read_buffer());
                 }
 
-                let public_key = &mut self.crypto.public_key.serialize().clone();
+                // agree on the features enabled on both sides
+                let features =
+                    ProtocolFeatures::parse(request.header(X_PROTOCOL_CTL).unwrap_or_default())
+                        .intersection(&hs_cfg.protocol_features);
+
+                // the initiator must have signed the same shared value
+                let peer_info = verify_peer(
+                    &self.crypto,
+                    &request,
+                    &shared_value,
+                    features,
+                    hs_cfg.http_instance_cookie,
+                )
+                .map_err(|e| {
+                    error!(parent: self.node().span(), "invalid handshake request from {addr}: {e}");
+                    e
+                })?;
+
+                let mut public_key = self.crypto.public_key();
+                let mut signed_value = shared_value.clone();
                 // introduce intentional errors into handshake if needed
                 if hs_cfg.bitflip_shared_val {
-                    randomly_flip_bit(&mut shared_value);
+                    randomly_flip_bit(&mut signed_value);
                 }
                 if hs_cfg.bitflip_pub_key {
-                    randomly_flip_bit(public_key.as_mut_slice());
+                    randomly_flip_bit(&mut public_key);
                 }
                 // base58-encode the public key and create the session signature
-                let base58_pk = encode_base58(NodeType::Public, public_key);
-                let sig = create_session_signature(&self.crypto, &shared_value);
+                let base58_pk = encode_base58(NodeType::Public, &public_key);
+                let sig = create_session_signature(&self.crypto, &signed_value);
 
                 // prepare the response
-                let mut rsp = Vec::new();
-                let mut rsp_header = |mut header: String| {
-                    header.push_str("\r\n");
-                    rsp.extend_from_slice(header.as_bytes());
-                };
-
-                rsp_header("HTTP/1.1 101 Switching Protocols".into());
-                rsp_header(format!("Connection: {}", hs_cfg.http_connection));
-                rsp_header(format!("Upgrade: {}", hs_cfg.http_upgrade_rsp));
-                rsp_header(format!("Connect-As: {}", hs_cfg.http_connect_as));
-                rsp_header(format!("Server: {}", hs_cfg.http_ident));
-                if let Some(ref crawl) = hs_cfg.http_crawl {
-                    rsp_header(format!("Crawl: {crawl}"))
-                };
-                rsp_header(format!("X-Protocol-Ctl: {}", hs_cfg.http_x_protocol_ctl));
-                if let Some(ref time) = hs_cfg.http_network_time {
-                    rsp_header(format!("Network-Time: {time}"))
-                };
-                rsp_header(format!("Public-Key: {base58_pk}"));
-                rsp_header(format!("Session-Signature: {sig}"));
-                if let Some(ref ledger) = hs_cfg.http_closed_ledger {
-                    rsp_header(format!("Closed-Ledger: {ledger}"))
-                };
-                if let Some(ref ledger) = hs_cfg.http_prev_ledger {
-                    rsp_header(format!("Previous-Ledger: {ledger}"))
-                };
-                if let Some(ref header) = hs_cfg.http_unexpected_extra_field_and_value {
-                    rsp_header(header.clone())
+                let lines = match hs_cfg.http_lines {
+                    Some(ref lines) => lines.clone(),
+                    None => hs_cfg.response_lines(&features),
                 };
-                rsp_header("".into()); // An HTTP header ends with '\r\n'
+                let rsp = hs_cfg.serialize_lines(&lines, &base58_pk, &sig);
 
                 // send the handshake HTTP response message
-                let rsp = Bytes::from(rsp);
                 trace!(parent: self.node().span(), "responding to {addr} with {rsp:?}");
-                framed.send(rsp).await?;
+                send_message(framed.get_mut(), &rsp, hs_cfg.trickle).await?;
+                self.set_peer_info(addr, peer_info);
 
                 tls_stream
             }
@@ -368,3 +865,165 @@ fn randomly_flip_bit(arr: &mut [u8]) {
     let idx = thread_rng().gen_range(0..arr.len());
     arr[idx] ^= 1 << thread_rng().gen_range(0..8);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{protocol::codecs::http::StartLine, tools::inner_node::KeyType};
+
+    #[test]
+    fn session_signature_roundtrip() {
+        for key_type in [KeyType::Secp256k1, KeyType::Ed25519] {
+            let crypto = Crypto::new(key_type, true);
+
+            let base58_pk = encode_base58(NodeType::Public, &crypto.public_key());
+            assert!(base58_pk.starts_with('n'));
+            let public_key = decode_base58(NodeType::Public, &base58_pk).unwrap();
+            assert_eq!(public_key, crypto.public_key());
+            assert!(decode_base58(NodeType::Private, &base58_pk).is_none());
+
+            let mut shared_value = [7u8; 32];
+            let sig = STANDARD
+                .decode(create_session_signature(&crypto, &shared_value))
+                .unwrap();
+            assert!(verify_session_signature(&crypto, &public_key, &shared_value, &sig).is_ok());
+
+            shared_value[0] ^= 1;
+            assert!(matches!(
+                verify_session_signature(&crypto, &public_key, &shared_value, &sig),
+                Err(VerificationError::SignatureMismatch)
+            ));
+        }
+    }
+
+    #[test]
+    fn rejection_with_redirects() {
+        let response = HttpHandshakeMessage {
+            start_line: StartLine::Response {
+                status: 503,
+                reason: "Service Unavailable".into(),
+            },
+            version: 1,
+            headers: Default::default(),
+            body: Bytes::from_static(br#"{"peer-ips":["10.0.0.1:51235","[::1]:2459","bad"]}"#),
+        };
+
+        assert_eq!(
+            HandshakeOutcome::rejected(&response),
+            HandshakeOutcome::Rejected {
+                status: 503,
+                redirect_peers: vec![
+                    "10.0.0.1:51235".parse().unwrap(),
+                    "[::1]:2459".parse().unwrap()
+                ],
+            }
+        );
+
+        // Other errors don't come with a JSON body.
+        let response = HttpHandshakeMessage {
+            start_line: StartLine::Response {
+                status: 400,
+                reason: "Bad Request".into(),
+            },
+            body: Bytes::from_static(b"Invalid handshake"),
+            ..response
+        };
+        assert_eq!(
+            HandshakeOutcome::rejected(&response),
+            HandshakeOutcome::Rejected {
+                status: 400,
+                redirect_peers: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn instance_cookie_and_addresses() {
+        let crypto = Crypto::new(KeyType::Secp256k1, true);
+        let shared_value = [7u8; 32];
+
+        let mut headers = BTreeMap::new();
+        headers.insert(
+            PUBLIC_KEY.to_lowercase(),
+            encode_base58(NodeType::Public, &crypto.public_key()),
+        );
+        headers.insert(
+            SESSION_SIGNATURE.to_lowercase(),
+            create_session_signature(&crypto, &shared_value),
+        );
+        headers.insert(INSTANCE_COOKIE.to_lowercase(), "42".into());
+        headers.insert(REMOTE_IP.to_lowercase(), "203.0.113.7".into());
+        let mut message = HttpHandshakeMessage {
+            start_line: StartLine::Request {
+                method: "GET".into(),
+                path: "/".into(),
+            },
+            version: 1,
+            headers,
+            body: Bytes::new(),
+        };
+        let verify = |message: &HttpHandshakeMessage, own_cookie| {
+            verify_peer(
+                &crypto,
+                message,
+                &shared_value,
+                Default::default(),
+                own_cookie,
+            )
+        };
+
+        let info = verify(&message, Some(7)).unwrap();
+        assert_eq!(info.instance_cookie, Some(42));
+        assert_eq!(info.remote_ip, Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(info.local_ip, None);
+
+        assert!(matches!(
+            verify(&message, Some(42)),
+            Err(VerificationError::SelfConnection)
+        ));
+
+        message
+            .headers
+            .insert(INSTANCE_COOKIE.to_lowercase(), "0".into());
+        assert!(matches!(
+            verify(&message, None),
+            Err(VerificationError::InvalidHeader(INSTANCE_COOKIE))
+        ));
+    }
+
+    #[test]
+    fn ledger_hash_encodings() {
+        let hash = parse_ledger_hash("6bsyOYDSAux+Ubqyqo41NT+ce9q1m/FzeOrdTQSG758=").unwrap();
+        assert_eq!(parse_ledger_hash(&hex::encode_upper(hash)), Some(hash));
+        assert!(parse_ledger_hash("AAAA").is_none());
+    }
+
+    #[test]
+    fn serialize_raw_lines() {
+        let cfg = HandshakeCfg {
+            http_line_ending: "\n".into(),
+            ..Default::default()
+        };
+        let lines = [
+            "GET / HTTP/1.1".into(),
+            HandshakeLine::SessionSignature,
+            "upgrade: XRPL/2.2".into(),
+            HandshakeLine::PublicKey,
+        ];
+        assert_eq!(
+            cfg.serialize_lines(&lines, "n9Key", "c2ln"),
+            Bytes::from_static(
+                b"GET / HTTP/1.1\nSession-Signature: c2ln\nupgrade: XRPL/2.2\nPublic-Key: n9Key\n\n"
+            )
+        );
+
+        // The generated request contains the session fields once each.
+        let lines = HandshakeCfg::default().request_lines();
+        assert_eq!(lines[0], "GET / HTTP/1.1".into());
+        let count = |line: HandshakeLine| lines.iter().filter(|l| **l == line).count();
+        assert_eq!(count(HandshakeLine::PublicKey), 1);
+        assert_eq!(count(HandshakeLine::SessionSignature), 1);
+    }
+}
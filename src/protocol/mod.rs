@@ -1,7 +1,10 @@
 //! An implementation of the Ripple network protocol types and messages.
 
 pub mod codecs;
+pub mod disconnect;
+pub mod features;
 pub mod handshake;
 pub mod proto;
 pub mod reading;
+pub mod render;
 pub mod writing;
@@ -1,24 +1,82 @@
 use std::{io, net::SocketAddr};
 
+use bytes::BytesMut;
 use pea2pea::{protocols::Reading, ConnectionSide, Pea2Pea};
+use tokio_util::codec::Decoder;
 use tracing::*;
 
 use crate::{
-    protocol::codecs::message::{BinaryMessage, MessageCodec},
+    protocol::codecs::message::{
+        BinaryMessage, DecodeError, Header, MessageCodec, RawBinaryMessage,
+    },
     tools::inner_node::InnerNode,
 };
 
+/// An inbound message, either decoded upfront or left to be decoded on demand
+/// (see [`MessageCodecCfg::lazy_decoding`](crate::protocol::codecs::message::MessageCodecCfg)).
+#[derive(Debug)]
+pub enum InboundMessage {
+    Decoded(BinaryMessage),
+    Raw(RawBinaryMessage),
+}
+
+impl InboundMessage {
+    pub fn header(&self) -> &Header {
+        match self {
+            Self::Decoded(message) => &message.header,
+            Self::Raw(message) => &message.header,
+        }
+    }
+
+    /// Returns the decoded message, decoding its payload first if needed.
+    pub fn into_binary_message(self) -> Result<BinaryMessage, DecodeError> {
+        match self {
+            Self::Decoded(message) => Ok(message),
+            Self::Raw(message) => message.into_binary_message(),
+        }
+    }
+}
+
+/// A [`MessageCodec`] wrapper which skips decoding of payloads if lazy decoding is enabled.
+pub struct InboundCodec(MessageCodec);
+
+impl Decoder for InboundCodec {
+    type Item = InboundMessage;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if !self.0.cfg().lazy_decoding {
+            return Ok(self.0.decode(src)?.map(InboundMessage::Decoded));
+        }
+
+        match self.0.decode_raw_message(src) {
+            Ok(message) => Ok(message.map(InboundMessage::Raw)),
+            Err(e) => {
+                self.0.record_decode_error(&e);
+                Err(e.into())
+            }
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl Reading for InnerNode {
-    type Message = BinaryMessage;
-    type Codec = MessageCodec;
+    type Message = InboundMessage;
+    type Codec = InboundCodec;
 
-    fn codec(&self, _addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
-        Self::Codec::new(self.node().span().clone())
+    fn codec(&self, addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
+        InboundCodec(self.message_codec(addr))
     }
 
     async fn process_message(&self, source: SocketAddr, message: Self::Message) -> io::Result<()> {
-        debug!(parent: self.node().span(), "read a message from {}: {:?}", source, message.payload);
+        match &message {
+            InboundMessage::Decoded(message) => {
+                debug!(parent: self.node().span(), "read a message from {}: {}", source, message.payload);
+            }
+            InboundMessage::Raw(message) => {
+                debug!(parent: self.node().span(), "read a raw message from {}: {}", source, message.header);
+            }
+        }
         debug!(
             parent: self.node().span(),
             "sending the message to the node's inbound queue"
@@ -0,0 +1,643 @@
+//! Human-readable and JSON rendering of the peer messages.
+//!
+//! Public keys are rendered in base58 (as rippled displays them), hashes and other binary data
+//! in uppercase hex and enums by name. All the messages implement [`Display`](fmt::Display),
+//! printing the rendered message as compact JSON, and [`Serialize`].
+
+#![allow(deprecated)] // some of the proto fields are deprecated, but still sent by the nodes
+
+use std::fmt::{self, Write};
+
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::protocol::{
+    codecs::message::{BinaryMessage, Header, Payload},
+    handshake::{encode_base58, NodeType},
+    proto::*,
+};
+
+/// Rendering of a message as a JSON value.
+pub trait Render {
+    fn render(&self) -> Value;
+}
+
+macro_rules! impl_display_and_serialize {
+    ($($type:ty),* $(,)?) => {
+        $(
+            impl fmt::Display for $type {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{}", self.render())
+                }
+            }
+
+            impl Serialize for $type {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    self.render().serialize(serializer)
+                }
+            }
+        )*
+    };
+}
+
+impl_display_and_serialize!(
+    BinaryMessage,
+    Header,
+    Payload,
+    TmManifest,
+    TmManifests,
+    TmPing,
+    TmClusterNode,
+    TmLoadSource,
+    TmCluster,
+    tm_endpoints::TmEndpointv2,
+    TmEndpoints,
+    TmTransaction,
+    TmTransactions,
+    TmLedgerNode,
+    TmGetLedger,
+    TmLedgerData,
+    TmProposeSet,
+    TmStatusChange,
+    TmHaveTransactions,
+    TmHaveTransactionSet,
+    TmValidation,
+    TmIndexedObject,
+    TmGetObjectByHash,
+    TmValidatorList,
+    ValidatorBlobInfo,
+    TmValidatorListCollection,
+    TmSquelch,
+    TmProofPathRequest,
+    TmProofPathResponse,
+    TmReplayDeltaRequest,
+    TmReplayDeltaResponse,
+    TmPublicKey,
+    TmGetPeerShardInfoV2,
+    tm_peer_shard_info_v2::TmIncomplete,
+    TmPeerShardInfoV2,
+);
+
+impl Render for BinaryMessage {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("header", self.header.render())
+            .with("payload", self.payload.render())
+            .build()
+    }
+}
+
+impl Render for Header {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with(
+                "message_type",
+                name(self.message_type() as i32, MessageType::from_i32),
+            )
+            .with("compressed", self.is_compressed())
+            .with("wire_size", self.total_wire_size())
+            .with("uncompressed_size", self.uncompressed_size())
+            .build()
+    }
+}
+
+// A payload of a message type which isn't modelled is rendered with its raw bytes (see
+// `Payload::render`, generated along with `Payload`).
+pub(crate) fn render_unknown(message_type: u16, raw: &[u8]) -> Value {
+    Fields::default()
+        .with("message_type", message_type)
+        .with("raw", hex(raw))
+        .build()
+}
+
+/// Renders the value as indented `key: value` lines.
+pub fn to_text(value: &Value) -> String {
+    let mut text = String::new();
+    write_text(&mut text, value, 0);
+
+    text
+}
+
+fn write_text(text: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::Object(inner) if !inner.is_empty() => {
+                        writeln!(text, "{:indent$}{key}:", "").unwrap();
+                        write_text(text, value, indent + 2);
+                    }
+                    Value::Array(items) if !items.is_empty() => {
+                        writeln!(text, "{:indent$}{key}:", "").unwrap();
+                        write_text(text, value, indent + 2);
+                    }
+                    _ => writeln!(text, "{:indent$}{key}: {}", "", scalar(value)).unwrap(),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match item {
+                    Value::Object(_) | Value::Array(_) => {
+                        writeln!(text, "{:indent$}-", "").unwrap();
+                        write_text(text, item, indent + 2);
+                    }
+                    _ => writeln!(text, "{:indent$}- {}", "", scalar(item)).unwrap(),
+                }
+            }
+        }
+        _ => writeln!(text, "{:indent$}{}", "", scalar(value)).unwrap(),
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Object(_) => "{}".into(),
+        Value::Array(_) => "[]".into(),
+        _ => value.to_string(),
+    }
+}
+
+/// Encodes binary data in uppercase hex, as used by the rendered messages and the wire captures.
+pub fn to_hex(bytes: &[u8]) -> String {
+    hex::encode_upper(bytes)
+}
+
+fn hex(bytes: &[u8]) -> Value {
+    to_hex(bytes).into()
+}
+
+// Node public keys are rendered in base58, anything which doesn't look like one in hex.
+fn node_key(bytes: &[u8]) -> Value {
+    match bytes.len() {
+        33 => encode_base58(NodeType::Public, bytes).into(),
+        _ => hex(bytes),
+    }
+}
+
+// Enums are rendered by name, unknown values as numbers.
+fn name<E: fmt::Debug>(value: i32, from_i32: fn(i32) -> Option<E>) -> Value {
+    match from_i32(value) {
+        Some(known) => format!("{known:?}").into(),
+        None => value.into(),
+    }
+}
+
+fn list<T>(items: &[T], render: impl Fn(&T) -> Value) -> Value {
+    items.iter().map(render).collect::<Vec<_>>().into()
+}
+
+// Builds a rendered object, skipping the unset optional fields.
+#[derive(Default)]
+struct Fields(Map<String, Value>);
+
+impl Fields {
+    fn with(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.0.insert(name.into(), value.into());
+        self
+    }
+
+    fn with_opt<T: Into<Value>>(self, name: &str, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.with(name, value),
+            None => self,
+        }
+    }
+
+    fn build(self) -> Value {
+        Value::Object(self.0)
+    }
+}
+
+impl Render for TmManifest {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("stobject", hex(&self.stobject))
+            .build()
+    }
+}
+
+impl Render for TmManifests {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("list", list(&self.list, Render::render))
+            .with_opt("history", self.history)
+            .build()
+    }
+}
+
+impl Render for TmPing {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("type", name(self.r#type, tm_ping::PingType::from_i32))
+            .with_opt("seq", self.seq)
+            .with_opt("ping_time", self.ping_time)
+            .with_opt("net_time", self.net_time)
+            .build()
+    }
+}
+
+impl Render for TmClusterNode {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("public_key", self.public_key.as_str())
+            .with("report_time", self.report_time)
+            .with("node_load", self.node_load)
+            .with_opt("node_name", self.node_name.as_deref())
+            .with_opt("address", self.address.as_deref())
+            .build()
+    }
+}
+
+impl Render for TmLoadSource {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("name", self.name.as_str())
+            .with("cost", self.cost)
+            .with_opt("count", self.count)
+            .build()
+    }
+}
+
+impl Render for TmCluster {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("cluster_nodes", list(&self.cluster_nodes, Render::render))
+            .with("load_sources", list(&self.load_sources, Render::render))
+            .build()
+    }
+}
+
+impl Render for tm_endpoints::TmEndpointv2 {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("endpoint", self.endpoint.as_str())
+            .with("hops", self.hops)
+            .build()
+    }
+}
+
+impl Render for TmEndpoints {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("version", self.version)
+            .with("endpoints_v2", list(&self.endpoints_v2, Render::render))
+            .build()
+    }
+}
+
+impl Render for TmTransaction {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("raw_transaction", hex(&self.raw_transaction))
+            .with("status", name(self.status, TransactionStatus::from_i32))
+            .with_opt("receive_timestamp", self.receive_timestamp)
+            .with_opt("deferred", self.deferred)
+            .build()
+    }
+}
+
+impl Render for TmTransactions {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("transactions", list(&self.transactions, Render::render))
+            .build()
+    }
+}
+
+impl Render for TmLedgerNode {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("nodedata", hex(&self.nodedata))
+            .with_opt("nodeid", self.nodeid.as_deref().map(hex))
+            .build()
+    }
+}
+
+impl Render for TmGetLedger {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("itype", name(self.itype, TmLedgerInfoType::from_i32))
+            .with_opt("ltype", self.ltype.map(|v| name(v, TmLedgerType::from_i32)))
+            .with_opt("ledger_hash", self.ledger_hash.as_deref().map(hex))
+            .with_opt("ledger_seq", self.ledger_seq)
+            .with("node_ids", list(&self.node_i_ds, |id| hex(id)))
+            .with_opt("request_cookie", self.request_cookie)
+            .with_opt(
+                "query_type",
+                self.query_type.map(|v| name(v, TmQueryType::from_i32)),
+            )
+            .with_opt("query_depth", self.query_depth)
+            .build()
+    }
+}
+
+impl Render for TmLedgerData {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("ledger_hash", hex(&self.ledger_hash))
+            .with("ledger_seq", self.ledger_seq)
+            .with("type", name(self.r#type, TmLedgerInfoType::from_i32))
+            .with("nodes", list(&self.nodes, Render::render))
+            .with_opt("request_cookie", self.request_cookie)
+            .with_opt("error", self.error.map(|v| name(v, TmReplyError::from_i32)))
+            .build()
+    }
+}
+
+impl Render for TmProposeSet {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("propose_seq", self.propose_seq)
+            .with("current_tx_hash", hex(&self.current_tx_hash))
+            .with("node_pub_key", node_key(&self.node_pub_key))
+            .with("close_time", self.close_time)
+            .with("signature", hex(&self.signature))
+            .with("previousledger", hex(&self.previousledger))
+            .with(
+                "added_transactions",
+                list(&self.added_transactions, |h| hex(h)),
+            )
+            .with(
+                "removed_transactions",
+                list(&self.removed_transactions, |h| hex(h)),
+            )
+            .with_opt("checked_signature", self.checked_signature)
+            .with_opt("hops", self.hops)
+            .build()
+    }
+}
+
+impl Render for TmStatusChange {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with_opt(
+                "new_status",
+                self.new_status.map(|v| name(v, NodeStatus::from_i32)),
+            )
+            .with_opt(
+                "new_event",
+                self.new_event.map(|v| name(v, NodeEvent::from_i32)),
+            )
+            .with_opt("ledger_seq", self.ledger_seq)
+            .with_opt("ledger_hash", self.ledger_hash.as_deref().map(hex))
+            .with_opt(
+                "ledger_hash_previous",
+                self.ledger_hash_previous.as_deref().map(hex),
+            )
+            .with_opt("network_time", self.network_time)
+            .with_opt("first_seq", self.first_seq)
+            .with_opt("last_seq", self.last_seq)
+            .build()
+    }
+}
+
+impl Render for TmHaveTransactions {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("hashes", list(&self.hashes, |h| hex(h)))
+            .build()
+    }
+}
+
+impl Render for TmHaveTransactionSet {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("status", name(self.status, TxSetStatus::from_i32))
+            .with("hash", hex(&self.hash))
+            .build()
+    }
+}
+
+impl Render for TmValidation {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("validation", hex(&self.validation))
+            .with_opt("checked_signature", self.checked_signature)
+            .with_opt("hops", self.hops)
+            .build()
+    }
+}
+
+impl Render for TmIndexedObject {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with_opt("hash", self.hash.as_deref().map(hex))
+            .with_opt("node_id", self.node_id.as_deref().map(hex))
+            .with_opt("index", self.index.as_deref().map(hex))
+            .with_opt("data", self.data.as_deref().map(hex))
+            .with_opt("ledger_seq", self.ledger_seq)
+            .build()
+    }
+}
+
+impl Render for TmGetObjectByHash {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with(
+                "type",
+                name(self.r#type, tm_get_object_by_hash::ObjectType::from_i32),
+            )
+            .with("query", self.query)
+            .with_opt("seq", self.seq)
+            .with_opt("ledger_hash", self.ledger_hash.as_deref().map(hex))
+            .with_opt("fat", self.fat)
+            .with("objects", list(&self.objects, Render::render))
+            .build()
+    }
+}
+
+impl Render for TmValidatorList {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("manifest", hex(&self.manifest))
+            .with("blob", hex(&self.blob))
+            .with("signature", hex(&self.signature))
+            .with("version", self.version)
+            .build()
+    }
+}
+
+impl Render for ValidatorBlobInfo {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with_opt("manifest", self.manifest.as_deref().map(hex))
+            .with("blob", hex(&self.blob))
+            .with("signature", hex(&self.signature))
+            .build()
+    }
+}
+
+impl Render for TmValidatorListCollection {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("version", self.version)
+            .with("manifest", hex(&self.manifest))
+            .with("blobs", list(&self.blobs, Render::render))
+            .build()
+    }
+}
+
+impl Render for TmSquelch {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("squelch", self.squelch)
+            .with("validator_pub_key", node_key(&self.validator_pub_key))
+            .with_opt("squelch_duration", self.squelch_duration)
+            .build()
+    }
+}
+
+impl Render for TmProofPathRequest {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("key", hex(&self.key))
+            .with("ledger_hash", hex(&self.ledger_hash))
+            .with("type", name(self.r#type, TmLedgerMapType::from_i32))
+            .build()
+    }
+}
+
+impl Render for TmProofPathResponse {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("key", hex(&self.key))
+            .with("ledger_hash", hex(&self.ledger_hash))
+            .with("type", name(self.r#type, TmLedgerMapType::from_i32))
+            .with_opt("ledger_header", self.ledger_header.as_deref().map(hex))
+            .with("path", list(&self.path, |node| hex(node)))
+            .with_opt("error", self.error.map(|v| name(v, TmReplyError::from_i32)))
+            .build()
+    }
+}
+
+impl Render for TmReplayDeltaRequest {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("ledger_hash", hex(&self.ledger_hash))
+            .build()
+    }
+}
+
+impl Render for TmReplayDeltaResponse {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("ledger_hash", hex(&self.ledger_hash))
+            .with_opt("ledger_header", self.ledger_header.as_deref().map(hex))
+            .with("transaction", list(&self.transaction, |tx| hex(tx)))
+            .with_opt("error", self.error.map(|v| name(v, TmReplyError::from_i32)))
+            .build()
+    }
+}
+
+/// Rendered as the bare key, as it's only used in the peer chains.
+impl Render for TmPublicKey {
+    fn render(&self) -> Value {
+        node_key(&self.public_key)
+    }
+}
+
+impl Render for TmGetPeerShardInfoV2 {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("peer_chain", list(&self.peer_chain, Render::render))
+            .with("relays", self.relays)
+            .build()
+    }
+}
+
+impl Render for tm_peer_shard_info_v2::TmIncomplete {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("shard_index", self.shard_index)
+            .with("state", self.state)
+            .with_opt("progress", self.progress)
+            .build()
+    }
+}
+
+impl Render for TmPeerShardInfoV2 {
+    fn render(&self) -> Value {
+        Fields::default()
+            .with("timestamp", self.timestamp)
+            .with("incomplete", list(&self.incomplete, Render::render))
+            .with_opt("finalized", self.finalized.as_deref())
+            .with("public_key", node_key(&self.public_key))
+            .with("signature", hex(&self.signature))
+            .with("peer_chain", list(&self.peer_chain, Render::render))
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn render_squelch() {
+        let payload = Payload::TmSquelch(TmSquelch {
+            squelch: true,
+            validator_pub_key: vec![0x02; 33],
+            squelch_duration: None,
+        });
+
+        let rendered = payload.render();
+        assert_eq!(
+            rendered,
+            json!({
+                "TmSquelch": {
+                    "squelch": true,
+                    "validator_pub_key": encode_base58(NodeType::Public, &[0x02; 33]),
+                }
+            })
+        );
+        assert!(rendered["TmSquelch"]["validator_pub_key"]
+            .as_str()
+            .unwrap()
+            .starts_with('n'));
+    }
+
+    #[test]
+    fn render_unknown_payload() {
+        let message_type = MessageType::MtGetShardInfo as u16;
+        let payload = Payload::decode_from(message_type, &[0xab, 0xcd][..]).unwrap();
+
+        assert_eq!(payload.name(), "Unknown");
+        assert_eq!(
+            payload.render(),
+            json!({ "Unknown": { "message_type": message_type, "raw": "ABCD" } })
+        );
+    }
+
+    #[test]
+    fn render_as_text() {
+        let payload = Payload::TmHaveTransactions(TmHaveTransactions {
+            hashes: vec![vec![0xab; 2], vec![0x01, 0x02]],
+        });
+
+        assert_eq!(
+            to_text(&payload.render()),
+            "TmHaveTransactions:\n  hashes:\n    - ABAB\n    - 0102\n"
+        );
+    }
+
+    #[test]
+    fn display_and_serialize() {
+        let ping = TmPing {
+            r#type: tm_ping::PingType::PtPong as i32,
+            seq: Some(7),
+            ping_time: None,
+            net_time: None,
+        };
+        assert_eq!(ping.to_string(), r#"{"type":"PtPong","seq":7}"#);
+
+        let payload = Payload::TmPing(TmPing { r#type: 42, ..ping });
+        assert_eq!(
+            serde_json::to_string(&payload).unwrap(),
+            r#"{"TmPing":{"type":42,"seq":7}}"#
+        );
+    }
+}
@@ -6,7 +6,7 @@ use tokio_util::codec::Encoder;
 
 use crate::{
     protocol::codecs::message::{MessageCodec, Payload},
-    tools::inner_node::InnerNode,
+    tools::{capture::Direction, inner_node::InnerNode},
 };
 
 impl Encoder<Vec<u8>> for MessageCodec {
@@ -14,6 +14,7 @@ impl Encoder<Vec<u8>> for MessageCodec {
 
     fn encode(&mut self, message: Vec<u8>, dst: &mut BytesMut) -> Result<(), Self::Error> {
         dst.put_slice(&message);
+        self.record(Direction::Outbound, None, &message);
 
         Ok(())
     }
@@ -39,7 +40,7 @@ impl Writing for InnerNode {
     type Message = MessageOrBytes;
     type Codec = MessageCodec;
 
-    fn codec(&self, _addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
-        Self::Codec::new(self.node().span().clone())
+    fn codec(&self, addr: SocketAddr, _side: ConnectionSide) -> Self::Codec {
+        self.message_codec(addr)
     }
 }
@@ -109,6 +109,12 @@ impl RippledConfigFile {
         writeln!(&mut config_str, "1")?;
         writeln!(&mut config_str)?;
 
+        if config.enable_compression {
+            writeln!(&mut config_str, "[compression]")?;
+            writeln!(&mut config_str, "true")?;
+            writeln!(&mut config_str)?;
+        }
+
         writeln!(&mut config_str, "[ips_fixed]")?;
         for addr in &config.initial_peers {
             writeln!(&mut config_str, "{} {}", addr.ip(), addr.port())?;
@@ -168,6 +168,12 @@ impl NodeBuilder {
         self
     }
 
+    /// Enables the peer message compression.
+    pub fn enable_compression(mut self, enabled: bool) -> Self {
+        self.conf.enable_compression = enabled;
+        self
+    }
+
     /// Sets address to bind to.
     pub fn set_addr(mut self, addr: SocketAddr) -> Self {
         self.conf.local_addr = addr;
@@ -248,6 +254,8 @@ pub struct NodeConfig {
     pub enable_sharding: bool,
     /// Setting this option to true will enable clustering.
     pub enable_cluster: bool,
+    /// Setting this option to true will enable the peer message compression.
+    pub enable_compression: bool,
 }
 
 impl Default for NodeConfig {
@@ -261,6 +269,7 @@ impl Default for NodeConfig {
             log_to_stdout: false,
             enable_sharding: false,
             enable_cluster: false,
+            enable_compression: false,
         }
     }
 }
@@ -57,7 +57,7 @@ async fn c009_TM_SQUELCH_cannot_squelch_peer_ledger_proposals() {
     wait_for_state(&node.rpc_url(), "proposing".into()).await;
 
     // Connect synth node.
-    let mut synth_node = SyntheticNode::new(&Default::default()).await;
+    let mut synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node
         .connect(node.addr())
         .await
@@ -126,7 +126,7 @@ async fn c016_TM_SQUELCH_squelch_distant_validators() {
     wait_for_state(&peer_node.rpc_url(), "proposing".into()).await;
 
     // Connect a synth node.
-    let mut synth_node = SyntheticNode::new(&Default::default()).await;
+    let mut synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node
         .connect(peer_node.addr())
         .await
@@ -1,13 +1,25 @@
+use std::time::SystemTime;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use openssl::ssl::SslVersion;
+use reqwest::Client;
+use serde_json::Value;
 use tempfile::TempDir;
 
 use crate::{
-    protocol::codecs::message::BinaryMessage,
+    protocol::{
+        codecs::message::BinaryMessage,
+        features::ProtocolFeatures,
+        handshake::{HandshakeCfg, HandshakeOutcome},
+    },
     setup::{
-        constants::CONNECTION_TIMEOUT,
+        constants::{CONNECTION_TIMEOUT, TESTNET_NETWORK_ID},
         node::{Node, NodeType},
     },
     tests::conformance::{perform_expected_message_test, TestConfig},
-    tools::synth_node::SyntheticNode,
+    tools::{
+        config::SynthNodeCfg, inner_node::TlsCfg, rpc::get_server_info, synth_node::SyntheticNode,
+    },
     wait_until,
 };
 
@@ -25,7 +37,7 @@ async fn c001_handshake_when_node_receives_connection() {
         .expect("Unable to start node");
 
     // Start synthetic node.
-    let synth_node = SyntheticNode::new(&Default::default()).await;
+    let synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node.connect(node.addr()).await.unwrap();
 
     // This is only set post-handshake.
@@ -44,7 +56,7 @@ async fn c002_handshake_when_node_initiates_connection() {
     // crate::tools::synth_node::enable_tracing();
 
     // Start synthetic node.
-    let synth_node = SyntheticNode::new(&Default::default()).await;
+    let synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     let listening_addr = synth_node
         .start_listening()
         .await
@@ -76,3 +88,256 @@ async fn c006_node_should_not_send_any_messages_if_no_handshake() {
     perform_expected_message_test(TestConfig::default().with_handshake(None), &response_check)
         .await;
 }
+
+#[tokio::test]
+async fn c027_handshake_negotiates_protocol_features() {
+    // ZG-CONFORMANCE-027
+
+    // crate::tools::synth_node::enable_tracing();
+
+    // Offer all the features.
+    let cfg = SynthNodeCfg {
+        handshake: Some(HandshakeCfg {
+            protocol_features: ProtocolFeatures {
+                compression: true,
+                vp_reduce_relay: true,
+                tx_reduce_relay: true,
+                ledger_replay: true,
+            },
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    for enable_compression in [false, true] {
+        // Build and start the Ripple node.
+        let target = TempDir::new().expect("Can't build tmp dir");
+        let mut node = Node::builder()
+            .enable_compression(enable_compression)
+            .start(target.path(), NodeType::Stateless)
+            .await
+            .expect("Unable to start node");
+
+        // Start synthetic node.
+        let synth_node = SyntheticNode::new(&cfg).await.unwrap();
+        synth_node.connect(node.addr()).await.unwrap();
+
+        // The node is configured with the transaction reduce-relay and the ledger replay, while
+        // the validation/proposal reduce-relay is left disabled.
+        assert_eq!(
+            synth_node.negotiated_features(node.addr()),
+            Some(ProtocolFeatures {
+                compression: enable_compression,
+                vp_reduce_relay: false,
+                tx_reduce_relay: true,
+                ledger_replay: true,
+            })
+        );
+
+        // Shutdown both nodes
+        synth_node.shut_down().await;
+        node.stop().unwrap();
+    }
+}
+
+#[tokio::test]
+async fn c028_handshake_presents_verifiable_node_identity() {
+    // ZG-CONFORMANCE-028
+
+    // The Ripple epoch (2000-01-01T00:00:00Z) in Unix time.
+    const RIPPLE_EPOCH_OFFSET: u64 = 946684800;
+    // The maximum accepted difference between the node's and our clock, in seconds.
+    const NETWORK_TIME_TOLERANCE: u64 = 30;
+
+    // Build and start the Ripple node.
+    let target = TempDir::new().expect("Can't build tmp dir");
+    let mut node = Node::builder()
+        .start(target.path(), NodeType::Stateless)
+        .await
+        .expect("Unable to start node");
+
+    // Start synthetic node; the handshake fails if the node's session signature is invalid.
+    let synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
+    synth_node.connect(node.addr()).await.unwrap();
+
+    let peer_info = synth_node
+        .peer_info(node.addr())
+        .expect("the handshake wasn't verified");
+
+    // The node identifies itself with the key it reports over RPC.
+    let server_info = get_server_info(&node.rpc_url())
+        .await
+        .expect("unable to get the server info");
+    assert_eq!(
+        peer_info.public_key_base58(),
+        server_info.result.info.pubkey_node
+    );
+    assert!(peer_info
+        .ident
+        .as_deref()
+        .unwrap_or_default()
+        .starts_with("rippled-"));
+
+    // The node's network time should be close to ours.
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        - RIPPLE_EPOCH_OFFSET;
+    let network_time = peer_info.network_time.expect("missing the network time");
+    assert!(network_time.abs_diff(now) <= NETWORK_TIME_TOLERANCE);
+
+    // Shutdown both nodes
+    synth_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+async fn c029_handshake_rejects_peers_from_other_networks() {
+    // ZG-CONFORMANCE-029
+
+    // Build and start the Ripple node with a network identifier.
+    let target = TempDir::new().expect("Can't build tmp dir");
+    let mut node = Node::builder()
+        .network_id(TESTNET_NETWORK_ID)
+        .start(target.path(), NodeType::Stateless)
+        .await
+        .expect("Unable to start node");
+
+    let cases = [
+        (None, true),
+        (Some(TESTNET_NETWORK_ID.to_string()), true),
+        (Some((TESTNET_NETWORK_ID + 1).to_string()), false),
+        (Some("invalid".to_string()), false),
+    ];
+
+    for (network_id, accepted) in cases {
+        let cfg = SynthNodeCfg {
+            handshake: Some(HandshakeCfg {
+                http_network_id: network_id.clone(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let synth_node = SyntheticNode::new(&cfg).await.unwrap();
+        let outcome = synth_node.connect_with_outcome(node.addr()).await;
+
+        assert_eq!(
+            matches!(outcome, Ok(HandshakeOutcome::Accepted)),
+            accepted,
+            "Network-ID: {network_id:?}, outcome: {outcome:?}"
+        );
+        if accepted {
+            // The node announces its network as well.
+            let peer_info = synth_node.peer_info(node.addr()).unwrap();
+            assert_eq!(peer_info.network_id, Some(TESTNET_NETWORK_ID));
+        }
+
+        synth_node.shut_down().await;
+    }
+
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+async fn c030_handshake_private_peer_address_is_not_crawled() {
+    // ZG-CONFORMANCE-030
+
+    // Build and start the Ripple node.
+    let target = TempDir::new().expect("Can't build tmp dir");
+    let mut node = Node::builder()
+        .start(target.path(), NodeType::Stateless)
+        .await
+        .expect("Unable to start node");
+
+    // Start a public and a private synthetic node.
+    let crawl_cfg = |crawl: &str| SynthNodeCfg {
+        handshake: Some(HandshakeCfg {
+            http_crawl: Some(crawl.into()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let public_node = SyntheticNode::new(&crawl_cfg("public")).await.unwrap();
+    public_node.connect(node.addr()).await.unwrap();
+    let private_node = SyntheticNode::new(&crawl_cfg("private")).await.unwrap();
+    private_node.connect(node.addr()).await.unwrap();
+
+    // Query the node's peers; its certificate is self-signed.
+    let client = Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .expect("unable to build the web client");
+    let crawl = client
+        .get(format!("https://{}/crawl", node.addr()))
+        .send()
+        .await
+        .expect("unable to query /crawl")
+        .json::<Value>()
+        .await
+        .expect("invalid /crawl response");
+
+    // The peers are identified by their base64-encoded public keys.
+    let peer = |synth_node: &SyntheticNode| {
+        let public_key = STANDARD.encode(synth_node.public_key());
+        crawl["overlay"]["active"]
+            .as_array()
+            .expect("missing the active peers")
+            .iter()
+            .find(|peer| peer["public_key"] == public_key.as_str())
+            .cloned()
+            .expect("the peer isn't listed")
+    };
+
+    // Both peers are listed, but only the public one along with its address.
+    assert!(peer(&public_node).get("ip").is_some());
+    assert!(peer(&private_node).get("ip").is_none());
+
+    // Shutdown all nodes
+    public_node.shut_down().await;
+    private_node.shut_down().await;
+    node.stop().unwrap();
+}
+
+#[tokio::test]
+async fn c031_handshake_over_tls_versions() {
+    // ZG-CONFORMANCE-031
+
+    // Build and start the Ripple node
+    let target = TempDir::new().expect("Can't build tmp dir");
+    let mut node = Node::builder()
+        .start(target.path(), NodeType::Stateless)
+        .await
+        .expect("Unable to start node");
+
+    // The session signature is computed from the TLS Finished messages, whose form depends on the
+    // protocol version.
+    let versions = [
+        ("TLS 1.2", SslVersion::TLS1_2),
+        ("TLS 1.3", SslVersion::TLS1_3),
+    ];
+    for (version_name, version) in versions {
+        for session_tickets in [true, false] {
+            let cfg = SynthNodeCfg {
+                tls: TlsCfg {
+                    min_proto_version: Some(version),
+                    max_proto_version: Some(version),
+                    renegotiation: false,
+                    session_tickets,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let synth_node = SyntheticNode::new(&cfg).await.unwrap();
+
+            synth_node.connect(node.addr()).await.unwrap_or_else(|e| {
+                panic!("{version_name}, session tickets: {session_tickets}: {e}")
+            });
+            assert!(synth_node.peer_info(node.addr()).is_some());
+
+            synth_node.shut_down().await;
+        }
+    }
+
+    node.stop().unwrap();
+}
@@ -76,7 +76,7 @@ async fn perform_expected_message_test(
         .unwrap();
 
     // Start synth node and connect to Ripple
-    let mut synth_node = SyntheticNode::new(&config.synth_node_cfg).await;
+    let mut synth_node = SyntheticNode::new(&config.synth_node_cfg).await.unwrap();
     synth_node.connect(node.addr()).await.unwrap();
 
     // Send the query message (if present)
@@ -113,7 +113,7 @@ pub async fn perform_testnet_transaction_check(check: &dyn Fn(&BinaryMessage) ->
     .expect("Unable to get the account data.");
 
     // Start a synthetic node and connect to the second node in the testnet.
-    let mut synth_node = SyntheticNode::new(&Default::default()).await;
+    let mut synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node
         .connect(testnet.running[NODE_IDS[1]].addr())
         .await
@@ -198,12 +198,12 @@ async fn c026_TM_VALIDATOR_LIST_send_validator_list() {
         .expect(ERR_NODE_BUILD);
 
     // create & connect two synth nodes
-    let synth_node1 = SyntheticNode::new(&Default::default()).await;
+    let synth_node1 = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node1
         .connect(node.addr())
         .await
         .expect(ERR_SYNTH_CONNECT);
-    let mut synth_node2 = SyntheticNode::new(&Default::default()).await;
+    let mut synth_node2 = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node2
         .connect(node.addr())
         .await
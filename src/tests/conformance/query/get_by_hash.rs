@@ -59,7 +59,7 @@ async fn c007_TM_GET_OBJECT_BY_HASH_get_transaction_by_hash() {
             ledger_seq: None,
         }],
     });
-    let mut synth_node = SyntheticNode::new(&Default::default()).await;
+    let mut synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node
         .connect(node.addr())
         .await
@@ -109,7 +109,7 @@ async fn c008_TM_HAVE_TRANSACTIONS_query_for_transactions_after_have_transaction
     let payload = Payload::TmHaveTransactions(TmHaveTransactions {
         hashes: vec![tx_hash.to_vec()],
     });
-    let mut synth_node = SyntheticNode::new(&Default::default()).await;
+    let mut synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node
         .connect(node.addr())
         .await
@@ -61,12 +61,12 @@ async fn check_relay_for_key_type(key_type: u8, relays: u32) {
         .expect(ERR_NODE_BUILD);
 
     // Create two synthetic nodes and connect them to rippled.
-    let synth_node1 = SyntheticNode::new(&Default::default()).await;
+    let synth_node1 = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node1
         .connect(node.addr())
         .await
         .expect(ERR_SYNTH_CONNECT);
-    let mut synth_node2 = SyntheticNode::new(&Default::default()).await;
+    let mut synth_node2 = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node2
         .connect(node.addr())
         .await
@@ -116,7 +116,7 @@ async fn c023_TM_PEER_SHARD_INFO_V2_node_should_respond_with_shard_info_if_shard
     wait_for_state(&node.rpc_url(), "proposing".into()).await;
 
     // Create a synthetic node and connect it to rippled.
-    let mut synth_node = SyntheticNode::new(&Default::default()).await;
+    let mut synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node
         .connect(node.addr())
         .await
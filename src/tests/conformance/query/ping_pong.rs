@@ -64,7 +64,7 @@ async fn c003_t2_TM_PING_expect_ping() {
         .expect(ERR_NODE_BUILD);
 
     // Create a synthetic node and connect it to the node.
-    let mut synth_node = SyntheticNode::new(&Default::default()).await;
+    let mut synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node
         .connect(node.addr())
         .await
@@ -124,7 +124,7 @@ async fn c003_t3_TM_PING_send_pong() {
         .expect("unable to start the rippled node");
 
     // Create a synthetic node and connect it to rippled.
-    let synth_node = SyntheticNode::new(&Default::default()).await;
+    let synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node
         .connect(node.addr())
         .await
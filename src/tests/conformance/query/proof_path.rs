@@ -26,7 +26,7 @@ async fn c025_TM_PROOF_PATH_REQUEST_TM_PROOF_PATH_RESPONSE_send_req_expect_rsp()
     assert!(!ledger_info.result.ledger.account_state.is_empty());
 
     // Create a synthetic node and connect it to rippled.
-    let mut synth_node = SyntheticNode::new(&Default::default()).await;
+    let mut synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node
         .connect(node.addr())
         .await
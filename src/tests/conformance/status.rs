@@ -21,7 +21,7 @@ async fn c010_TM_STATUS_CHANGE_node_should_send_ledger_information_using_status_
         .expect("unable to start stateful node");
 
     // Connect synth node.
-    let mut sn = SyntheticNode::new(&Default::default()).await;
+    let mut sn = SyntheticNode::new(&Default::default()).await.unwrap();
     sn.connect(node.addr()).await.unwrap();
 
     // Get ledger information via RPC.
@@ -173,9 +173,9 @@ async fn node_run_forever(cfg: DevTestCfg) {
     // SyntheticNode is spawned only if option is chosen in cfg options.
     let mut initial_peers = vec![];
     let mut synth_node: Option<SyntheticNode> = match cfg.synth_node {
-        SynthNodeOpt::On_TryToConnect(cfg) => Some(SyntheticNode::new(&cfg).await),
+        SynthNodeOpt::On_TryToConnect(cfg) => Some(SyntheticNode::new(&cfg).await.unwrap()),
         SynthNodeOpt::On_OnlyListening(cfg) => {
-            let sn = SyntheticNode::new(&cfg).await;
+            let sn = SyntheticNode::new(&cfg).await.unwrap();
             let listening_addr = sn.start_listening().await.expect(ERR_SYNTH_START_LISTENING);
             initial_peers.push(listening_addr);
             Some(sn)
@@ -15,6 +15,7 @@ use ziggurat_core_utils::err_constants::{
 };
 
 use crate::{
+    protocol::handshake::HandshakeOutcome,
     setup::node::{Node, NodeType},
     tools::{config::SynthNodeCfg, ips::IPS, synth_node::SyntheticNode},
 };
@@ -252,19 +253,25 @@ async fn p002_connections_load() {
 async fn simulate_peer(node_addr: SocketAddr, handshake_complete: Sender<()>, socket: TcpSocket) {
     let config = SynthNodeCfg::default();
 
-    let mut synth_node = SyntheticNode::new(&config).await;
+    let mut synth_node = SyntheticNode::new(&config).await.unwrap();
 
     // Establish peer connection
-    let handshake_result = synth_node.connect_from(node_addr, socket).await;
+    let handshake_result = synth_node
+        .connect_from_with_outcome(node_addr, socket)
+        .await;
     handshake_complete.send(()).await.unwrap();
     match handshake_result {
-        Ok(_) => {
+        Ok(HandshakeOutcome::Accepted) => {
             metrics::counter!(METRIC_ACCEPTED, 1);
         }
-        Err(_err) => {
+        Ok(HandshakeOutcome::Rejected { .. }) => {
             metrics::counter!(METRIC_REJECTED, 1);
             return;
         }
+        Err(_err) => {
+            metrics::counter!(METRIC_ERROR, 1);
+            return;
+        }
     };
 
     // Keep connection alive by consuming messages
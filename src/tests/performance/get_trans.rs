@@ -18,13 +18,15 @@ use ziggurat_core_utils::err_constants::{
 
 use crate::{
     protocol::{
-        codecs::message::Payload,
+        codecs::message::{MessageCodecCfg, Payload},
         proto::{
-            tm_get_object_by_hash::ObjectType, TmGetObjectByHash, TmIndexedObject, TmTransactions,
+            tm_get_object_by_hash::ObjectType, MessageType, TmGetObjectByHash, TmIndexedObject,
+            TmTransactions,
         },
     },
     setup::node::{Node, NodeType},
     tools::{
+        config::SynthNodeCfg,
         constants::{EXPECTED_RESULT_TIMEOUT, TEST_ACCOUNT},
         ips::IPS,
         rpc::{get_transaction_info, wait_for_account_data, wait_for_state},
@@ -173,7 +175,16 @@ async fn p003_t1_GET_TRANSACTION_latency() {
 
 #[allow(unused_must_use)] // just for result of the timeout
 async fn simulate_peer(node_addr: SocketAddr, socket: TcpSocket, tx_hash: [u8; TX_HASH_LEN]) {
-    let mut synth_node = SyntheticNode::new(&Default::default()).await;
+    // Only the transaction responses are of interest, so there's no point in decoding any other
+    // messages (e.g. the heavy validation traffic).
+    let config = SynthNodeCfg {
+        codec: MessageCodecCfg {
+            lazy_decoding: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut synth_node = SyntheticNode::new(&config).await.unwrap();
 
     // Establish peer connection
     synth_node
@@ -213,10 +224,14 @@ async fn simulate_peer(node_addr: SocketAddr, socket: TcpSocket, tx_hash: [u8; T
         // In every other case we simply move out and go to another request iteration.
         timeout(RESPONSE_TIMEOUT, async {
             loop {
-                let m = synth_node.recv_message().await;
+                let (_, m) = synth_node.recv_inbound_message().await;
+                if m.header().message_type() != MessageType::MtTransactions as u16 {
+                    continue;
+                }
+
                 if matches!(
-                    &m.1.payload,
-                    Payload::TmTransactions(TmTransactions {transactions})
+                    m.into_binary_message().map(|m| m.payload),
+                    Ok(Payload::TmTransactions(TmTransactions {transactions}))
                     if transactions.len() == 1
                 ) {
                     metrics::histogram!(METRIC_LATENCY, duration_as_ms(now.elapsed()));
@@ -19,8 +19,8 @@ use ziggurat_core_utils::err_constants::{
 
 use crate::{
     protocol::{
-        codecs::message::Payload,
-        proto::{tm_ping::PingType, TmPing},
+        codecs::message::{MessageCodecCfg, Payload},
+        proto::{tm_ping::PingType, MessageType, TmPing},
     },
     setup::node::{Node, NodeType},
     tools::{config::SynthNodeCfg, ips::IPS, synth_node::SyntheticNode},
@@ -156,9 +156,16 @@ async fn p001_t1_PING_PONG_throughput() {
 
 #[allow(unused_must_use)] // just for result of the timeout
 async fn simulate_peer(node_addr: SocketAddr, socket: TcpSocket) {
-    let config = SynthNodeCfg::default();
+    // Only pongs are of interest, so there's no point in decoding any other messages.
+    let config = SynthNodeCfg {
+        codec: MessageCodecCfg {
+            lazy_decoding: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
 
-    let mut synth_node = SyntheticNode::new(&config).await;
+    let mut synth_node = SyntheticNode::new(&config).await.unwrap();
 
     // Establish peer connection
     synth_node
@@ -195,14 +202,18 @@ async fn simulate_peer(node_addr: SocketAddr, socket: TcpSocket) {
         // In every other case we simply move out and go to another request iteration.
         timeout(RESPONSE_TIMEOUT, async {
             loop {
-                let m = synth_node.recv_message().await;
+                let (_, m) = synth_node.recv_inbound_message().await;
+                if m.header().message_type() != MessageType::MtPing as u16 {
+                    continue;
+                }
+
                 if matches!(
-                    &m.1.payload,
-                    Payload::TmPing(TmPing {
+                    m.into_binary_message().map(|m| m.payload),
+                    Ok(Payload::TmPing(TmPing {
                     r#type: r_type,
                     seq: Some(s),
                     ..
-                    }) if *s == seq && *r_type == PingType::PtPong as i32
+                    })) if s == seq && r_type == PingType::PtPong as i32
                 ) {
                     metrics::histogram!(METRIC_LATENCY, duration_as_ms(now.elapsed()));
                     break;
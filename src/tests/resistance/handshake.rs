@@ -1,36 +1,43 @@
 use std::{
+    collections::HashMap,
     net::{IpAddr, Ipv4Addr},
     time::SystemTime,
 };
 
+use openssl::ssl::SslVersion;
 use pea2pea::{
     ConnectionSide,
     ConnectionSide::{Initiator, Responder},
 };
+use serde::Deserialize;
 use tempfile::TempDir;
 use tokio::time::{sleep, Duration};
+use toml::{value::Table, Value};
 use ziggurat_core_utils::err_constants::{ERR_NODE_BUILD, ERR_NODE_STOP, ERR_TEMPDIR_NEW};
 
 use crate::{
-    protocol::{codecs::message::BinaryMessage, handshake::HandshakeCfg},
+    protocol::{
+        codecs::message::BinaryMessage,
+        handshake::{self, encode_base58, HandshakeCfg, HandshakeLine, HandshakeOutcome},
+    },
     setup::{
         constants::CONNECTION_TIMEOUT,
         node::{ChildExitCode, Node, NodeType},
     },
     tools::{
         config::SynthNodeCfg,
+        inner_node::{Crypto, KeyType, TlsCfg},
         synth_node::{self, SyntheticNode},
     },
     wait_until,
 };
 
-// Empirical values based on some unofficial testing.
-const WS_HTTP_HEADER_MAX_SIZE: usize = 7700;
-const WS_HTTP_HEADER_INVALID_SIZE: usize = WS_HTTP_HEADER_MAX_SIZE + 300;
-
 // Number of seconds between unix and ripple epoch.
 const RIPPLE_EPOCH_OFFSET: u64 = 946684800;
 
+// The OpenSSL cipher list which enables the legacy TLS versions.
+const LEGACY_CIPHER_LIST: &str = "DEFAULT:@SECLEVEL=0";
+
 #[allow(non_snake_case)]
 #[tokio::test]
 async fn r001_t1_HANDSHAKE_reject_if_user_agent_too_long() {
@@ -50,14 +57,14 @@ async fn r001_t1_HANDSHAKE_reject_if_user_agent_too_long() {
         hs_cfg
     });
 
-    let synth_node1 = SyntheticNode::new(&cfg).await;
+    let synth_node1 = SyntheticNode::new(&cfg).await.unwrap();
     // Ensure this connection was rejected by the node.
     assert!(synth_node1.connect(node.addr()).await.is_err());
     assert_eq!(synth_node1.num_connected(), 0);
     assert!(!synth_node1.is_connected(node.addr()));
 
     // Start the second synthetic node with the default 'User-Agent'.
-    let synth_node2 = SyntheticNode::new(&Default::default()).await;
+    let synth_node2 = SyntheticNode::new(&Default::default()).await.unwrap();
     synth_node2.connect(node.addr()).await.unwrap();
     // Ensure this connection was successful.
     assert_eq!(synth_node2.num_connected(), 1);
@@ -82,7 +89,7 @@ async fn r001_t2_HANDSHAKE_reject_if_server_too_long() {
         hs_cfg
     });
 
-    let synth_node1 = SyntheticNode::new(&cfg).await;
+    let synth_node1 = SyntheticNode::new(&cfg).await.unwrap();
     let sn1_listening_addr = synth_node1
         .start_listening()
         .await
@@ -91,7 +98,7 @@ async fn r001_t2_HANDSHAKE_reject_if_server_too_long() {
     // Start the second synthetic node with the default 'Server' header.
     let mut cfg2 = SynthNodeCfg::default();
     cfg2.pea2pea_config.listener_ip = Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 3)));
-    let synth_node2 = SyntheticNode::new(&cfg2).await;
+    let synth_node2 = SyntheticNode::new(&cfg2).await.unwrap();
     let sn2_listening_addr = synth_node2
         .start_listening()
         .await
@@ -159,7 +166,7 @@ async fn run_handshake_req_test_with_cfg(cfg: SynthNodeCfg, debug: Debug) -> boo
         .expect(ERR_NODE_BUILD);
 
     // Create a synthetic node and enable handshaking.
-    let mut synthetic_node = SyntheticNode::new(&cfg).await;
+    let mut synthetic_node = SyntheticNode::new(&cfg).await.unwrap();
 
     // Connect to the node and initiate the handshake.
     let handshake_established = if synthetic_node.connect(node.addr()).await.is_err() {
@@ -195,57 +202,132 @@ async fn normal_handshake() {
     );
 }
 
-/// Generate a string with a given length.
-fn gen_huge_string(len: usize) -> String {
-    vec!['y'; len].into_iter().collect::<String>()
+// The handshake cases of the table-driven tests, grouped by test (see the file for the format).
+const HANDSHAKE_CASES: &str = include_str!("handshake_cases.toml");
+
+/// A handshake variant along with its expected outcome.
+#[derive(Deserialize)]
+struct HandshakeCase {
+    /// What the case covers.
+    description: String,
+    /// Whether the node should accept the handshake.
+    accepted: bool,
+    /// The fields of the synthetic node configuration overriding the default ones.
+    #[serde(default)]
+    node: Table,
 }
 
-#[allow(non_snake_case)]
-#[tokio::test]
-async fn r001_t3_HANDSHAKE_connection_field() {
-    // ZG-RESISTANCE-001
-    // Expected valid value for the "Connection" field in the handshake should be "Upgrade".
+impl HandshakeCase {
+    fn node_cfg(&self) -> SynthNodeCfg {
+        let mut node = Value::Table(self.node.clone());
+        expand_padded_strings(&mut node);
 
-    let debug = Debug::disable();
+        node.try_into()
+            .unwrap_or_else(|e| panic!("invalid handshake case \"{}\": {e}", self.description))
+    }
+}
 
-    let gen_cfg = |connection: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
-            http_connection: connection,
-            ..Default::default()
-        }),
-        ..Default::default()
+/// A string padded to the given length, given as
+/// `{ pad = { value = "...", fill = "y", len = 7700 } }`.
+#[derive(Deserialize)]
+struct PaddedString {
+    /// The start of the string.
+    #[serde(default)]
+    value: String,
+    /// The padding character.
+    #[serde(default = "PaddedString::default_fill")]
+    fill: char,
+    /// The length of the padded string.
+    len: usize,
+}
+
+impl PaddedString {
+    fn default_fill() -> char {
+        ' '
+    }
+
+    fn expand(self) -> String {
+        let padding = self.len.saturating_sub(self.value.chars().count());
+
+        self.value + &self.fill.to_string().repeat(padding)
+    }
+}
+
+// Replaces the `{ pad = { ... } }` tables (see `PaddedString`) with the padded strings; all the
+// other tables are left as they are.
+fn expand_padded_strings(value: &mut Value) {
+    let Value::Table(table) = value else {
+        return;
     };
 
-    // Valid scenarios:
+    match table.get("pad") {
+        Some(pad) if table.len() == 1 => {
+            let padded: PaddedString = pad
+                .clone()
+                .try_into()
+                .unwrap_or_else(|e| panic!("invalid padded string {pad}: {e}"));
+            *value = Value::String(padded.expand());
+        }
+        _ => table.values_mut().for_each(expand_padded_strings),
+    }
+}
 
-    // These are also valid, but should they be?
-    let cfg = gen_cfg("upgrade".to_owned());
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-    let cfg = gen_cfg("uPgRAdE".to_owned());
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
+// Reads the handshake cases of the given group.
+fn handshake_cases(group: &str) -> Vec<HandshakeCase> {
+    let mut groups: HashMap<String, Vec<HandshakeCase>> =
+        toml::from_str(HANDSHAKE_CASES).expect("invalid handshake cases");
 
-    // Below tests assert the connection shouldn't be established.
+    groups
+        .remove(group)
+        .unwrap_or_else(|| panic!("no handshake cases for {group}"))
+}
 
-    // Field is almost correct.
-    let cfg = gen_cfg("Upgrad".to_owned());
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
-    let cfg = gen_cfg("Upgradee".to_owned());
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
-    let cfg = gen_cfg("UpgradeUpgrade".to_owned());
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+// Runs the handshake cases of the given group, asserting that each has the expected outcome.
+async fn run_handshake_cases(group: &str, debug: Debug) {
+    let mut unexpected = Vec::new();
+    for case in handshake_cases(group) {
+        if run_handshake_req_test_with_cfg(case.node_cfg(), debug).await != case.accepted {
+            unexpected.push(case.description);
+        }
+    }
 
-    // Find the largest instance value which the node could accept, but won't due to invalid value
-    // in the field.
-    let cfg = gen_cfg(gen_huge_string(WS_HTTP_HEADER_MAX_SIZE));
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+    assert!(
+        unexpected.is_empty(),
+        "unexpected outcome of the {group} handshake cases: {unexpected:?}"
+    );
+}
 
-    // Use a huge value which the node will always reject.
-    let cfg = gen_cfg(gen_huge_string(WS_HTTP_HEADER_INVALID_SIZE));
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+#[test]
+fn handshake_cases_are_valid() {
+    let groups: HashMap<String, Vec<HandshakeCase>> =
+        toml::from_str(HANDSHAKE_CASES).expect("invalid handshake cases");
 
-    // Send an empty field.
-    let cfg = gen_cfg(String::new());
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+    for case in groups.values().flatten() {
+        assert!(case.node_cfg().handshake.is_some());
+    }
+
+    // The padded values.
+    let case = handshake_cases("upgrade_req")
+        .into_iter()
+        .find(|case| case.description == "the largest acceptable size")
+        .unwrap();
+    let upgrade = case.node_cfg().handshake.unwrap().http_upgrade_req;
+    assert_eq!(upgrade.len(), 7700);
+    assert_eq!(upgrade.trim_end(), "XRPL/2.2");
+
+    // Only the tables tagged with `pad` are expanded.
+    let mut untagged: Value = toml::from_str("len = 7700").unwrap();
+    expand_padded_strings(&mut untagged);
+    assert_eq!(untagged.get("len"), Some(&Value::Integer(7700)));
+}
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r001_t3_HANDSHAKE_connection_field() {
+    // ZG-RESISTANCE-001
+    // Expected valid value for the "Connection" field in the handshake should be "Upgrade".
+
+    run_handshake_cases("connection", Debug::disable()).await;
 }
 
 #[allow(non_snake_case)]
@@ -255,32 +337,7 @@ async fn r001_t5_HANDSHAKE_crawl_field() {
     // Expected valid value for the "Crawl" field in the handshake should be "Public" (case insensitive).
     // Other values are considered to be "Private".
 
-    let debug = Debug::disable();
-
-    let gen_cfg = |crawl: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
-            http_crawl: Some(crawl),
-            ..Default::default()
-        }),
-        ..Default::default()
-    };
-
-    // Valid scenarios:
-
-    // This is also valid, but should it be? The node checks for "public" to determine whether it's public,
-    // everything else is considered not public.
-    let cfg = gen_cfg("Bazinga".to_owned());
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-    let cfg = gen_cfg(String::new());
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-    let cfg = gen_cfg(gen_huge_string(WS_HTTP_HEADER_MAX_SIZE));
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-
-    // Invalid scenarios:
-
-    // Use a huge value that the node will always reject.
-    let cfg = gen_cfg(gen_huge_string(WS_HTTP_HEADER_INVALID_SIZE));
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+    run_handshake_cases("crawl", Debug::disable()).await;
 }
 
 #[allow(non_snake_case)]
@@ -289,46 +346,7 @@ async fn r001_t4_HANDSHAKE_connect_as_field() {
     // ZG-RESISTANCE-001
     // Expected valid value for the "Connect-As" field in the handshake should be "Peer".
 
-    let debug = Debug::disable();
-
-    let gen_cfg = |connect_as: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
-            http_connect_as: connect_as,
-            ..Default::default()
-        }),
-        ..Default::default()
-    };
-
-    // Valid scenarios:
-
-    // These are also valid, but should they be?
-    let cfg = gen_cfg("peer".to_owned());
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-    let cfg = gen_cfg("PeER".to_owned());
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-
-    // Below tests assert the connection shouldn't be established.
-
-    // Field is almost correct.
-    let cfg = gen_cfg("Pee".to_owned());
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
-    let cfg = gen_cfg("Peerr".to_owned());
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
-    let cfg = gen_cfg("PeerPeer".to_owned());
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
-
-    // Find the largest instance value that the node could accept, but won't due to invalid value
-    // in the field.
-    let cfg = gen_cfg(gen_huge_string(WS_HTTP_HEADER_MAX_SIZE));
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
-
-    // Use a huge value that the node will always reject.
-    let cfg = gen_cfg(gen_huge_string(WS_HTTP_HEADER_INVALID_SIZE));
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
-
-    // Send an empty field.
-    let cfg = gen_cfg(String::new());
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+    run_handshake_cases("connect_as", Debug::disable()).await;
 }
 
 #[allow(non_snake_case)]
@@ -337,37 +355,7 @@ async fn r001_t6_HANDSHAKE_x_protocol_ctl_field() {
     // ZG-RESISTANCE-001
     // Expected valid value for the "X-Protocol-Ctl" field in the handshake should be a valid string.
 
-    let debug = Debug::disable();
-
-    let gen_cfg = |protocol: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
-            http_x_protocol_ctl: protocol,
-            ..Default::default()
-        }),
-        ..Default::default()
-    };
-
-    // These are also valid, but should they be?
-    let cfg = gen_cfg("leDgErrEpLay=하나;TXRR=да;".to_owned());
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-
-    let cfg = gen_cfg("leDgErrEpLay=I'm happy to accept anything really;".to_owned());
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-
-    let cfg = gen_cfg("unknown_option_here_is_fine=One11111!!;".to_owned());
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-
-    // Find the largest instance value which the node could accept.
-    let cfg = gen_cfg(gen_huge_string(WS_HTTP_HEADER_MAX_SIZE));
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-
-    // Send an empty field.
-    let cfg = gen_cfg(String::new());
-    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
-
-    // Use a huge value which the node will always reject.
-    let cfg = gen_cfg(gen_huge_string(WS_HTTP_HEADER_INVALID_SIZE));
-    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+    run_handshake_cases("x_protocol_ctl", Debug::disable()).await;
 }
 
 #[allow(non_snake_case)]
@@ -426,46 +414,125 @@ async fn r001_t8_HANDSHAKE_upgrade_req_field() {
     // ZG-RESISTANCE-001
     // Expected valid value for the "Upgrade" field in the handshake should be valid.
 
+    run_handshake_cases("upgrade_req", Debug::disable()).await;
+}
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r001_t9_HANDSHAKE_raw_header_lines() {
+    // ZG-RESISTANCE-001
+    // The handshake request is built line by line, so the header order, casing, start line and
+    // line endings can be tampered with.
+
     let debug = Debug::disable();
 
-    let gen_cfg = |version: String| SynthNodeCfg {
-        handshake: Some(HandshakeCfg {
-            http_upgrade_req: version,
+    let gen_cfg = |edit: &dyn Fn(&mut Vec<HandshakeLine>)| {
+        let mut hs_cfg = HandshakeCfg::default();
+        let mut lines = hs_cfg.request_lines();
+        edit(&mut lines);
+        hs_cfg.http_lines = Some(lines);
+
+        SynthNodeCfg {
+            handshake: Some(hs_cfg),
             ..Default::default()
-        }),
-        ..Default::default()
+        }
     };
 
-    // Valid value for Upgrade
-    let cfg = gen_cfg("XRPL/2.2".to_owned());
+    // The generated lines, unchanged.
+    let cfg = gen_cfg(&|_| {});
     assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
 
-    // Find the largest instance value which the node could accept.
-    let cfg = gen_cfg(format!("{:#7700}", "XRPL/2.2"));
+    // Valid values
+    // The headers in reverse order.
+    let cfg = gen_cfg(&|lines| lines[1..].reverse());
+    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    // Lowercase header names.
+    let cfg = gen_cfg(&|lines| {
+        for line in lines[1..].iter_mut() {
+            if let HandshakeLine::Raw(line) = line {
+                if let Some((name, value)) = line.split_once(':') {
+                    *line = format!("{}:{value}", name.to_lowercase());
+                }
+            }
+        }
+    });
+    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    // Duplicated headers; the node only considers the first occurrence.
+    let is_upgrade =
+        |line: &HandshakeLine| matches!(line, HandshakeLine::Raw(l) if l.starts_with("Upgrade:"));
+    let is_public_key = |line: &HandshakeLine| *line == HandshakeLine::PublicKey;
+    let other_public_key = other_public_key_line();
+
+    // A valid "Upgrade" field followed by an invalid one.
+    let cfg = gen_cfg(&|lines| insert_next_to(lines, is_upgrade, "Upgrade: XRPL/0.1", false));
+    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    // Our "Public-Key" field followed by a different one.
+    let cfg = gen_cfg(&|lines| insert_next_to(lines, is_public_key, &other_public_key, false));
     assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
 
     // Invalid values
-    // Large version.
-    let cfg = gen_cfg("XRPL/20.2".to_owned());
+    // An invalid "Upgrade" field followed by a valid one.
+    let cfg = gen_cfg(&|lines| insert_next_to(lines, is_upgrade, "Upgrade: XRPL/0.1", true));
     assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
 
-    // Negative version number.
-    let cfg = gen_cfg("XRPL/-2.2".to_owned());
+    // A different "Public-Key" field followed by ours, which the signature belongs to.
+    let cfg = gen_cfg(&|lines| insert_next_to(lines, is_public_key, &other_public_key, true));
     assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
 
-    // Send empty version.
-    let cfg = gen_cfg("XRPL/".to_owned());
+    // No "Upgrade" field.
+    let cfg = gen_cfg(&|lines| {
+        lines.retain(|line| !matches!(line, HandshakeLine::Raw(l) if l.starts_with("Upgrade:")))
+    });
     assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
 
-    // Send an empty field.
-    let cfg = gen_cfg(String::new());
+    // No "Public-Key" field.
+    let cfg = gen_cfg(&|lines| lines.retain(|line| *line != HandshakeLine::PublicKey));
     assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
 
-    // Use a huge value which the node will always reject.
-    let cfg = gen_cfg(format!("{:#8000}", "XRPL/2.2"));
+    // No "Session-Signature" field.
+    let cfg = gen_cfg(&|lines| lines.retain(|line| *line != HandshakeLine::SessionSignature));
+    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    // A different request method.
+    let cfg = gen_cfg(&|lines| lines[0] = "POST / HTTP/1.1".into());
+    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    // Bare LF line endings.
+    let mut cfg = gen_cfg(&|_| {});
+    if let Some(ref mut hs_cfg) = cfg.handshake {
+        hs_cfg.http_line_ending = "\n".to_owned();
+    }
     assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
 }
 
+// Inserts the line right before (or after) the first line matching the predicate.
+fn insert_next_to(
+    lines: &mut Vec<HandshakeLine>,
+    predicate: impl Fn(&HandshakeLine) -> bool,
+    line: &str,
+    before: bool,
+) {
+    let idx = lines
+        .iter()
+        .position(predicate)
+        .expect("no line matches the predicate");
+
+    lines.insert(if before { idx } else { idx + 1 }, line.into());
+}
+
+// A "Public-Key" field with a public key other than the synthetic node's.
+fn other_public_key_line() -> String {
+    let crypto = Crypto::new(KeyType::Secp256k1, true);
+
+    format!(
+        "Public-Key: {}",
+        encode_base58(handshake::NodeType::Public, &crypto.public_key())
+    )
+}
+
 #[allow(non_snake_case)]
 #[tokio::test]
 async fn r003_t1_HANDSHAKE_reject_if_public_key_has_bit_flipped() {
@@ -498,9 +565,25 @@ async fn r003_t2_HANDSHAKE_reject_if_shared_value_has_bit_flipped() {
     run_and_assert_handshake_failure(&cfg, Initiator).await;
 }
 
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r003_t3_HANDSHAKE_reject_if_public_key_is_ed25519() {
+    // ZG-RESISTANCE-003
+
+    // Prepare config for a synthetic node with an ed25519 identity. The session signature is
+    // valid, but rippled only accepts secp256k1 node public keys.
+    let cfg = SynthNodeCfg {
+        key_type: KeyType::Ed25519,
+        ..Default::default()
+    };
+
+    run_and_assert_handshake_failure(&cfg, Responder).await;
+    run_and_assert_handshake_failure(&cfg, Initiator).await;
+}
+
 async fn run_and_assert_handshake_failure(config: &SynthNodeCfg, connection_side: ConnectionSide) {
     // Start a SyntheticNode with the required config.
-    let synth_node = SyntheticNode::new(config).await;
+    let synth_node = SyntheticNode::new(config).await.unwrap();
     let listening_addr = synth_node
         .start_listening()
         .await
@@ -518,9 +601,10 @@ async fn run_and_assert_handshake_failure(config: &SynthNodeCfg, connection_side
         .await
         .expect("unable to start the node");
 
-    // Try to connect to rippled if Initiator side.
+    // Try to connect to rippled if Initiator side. The handshake fails as rippled doesn't answer
+    // with a verifiable response.
     if connection_side == Initiator {
-        assert!(synth_node.connect(node.addr()).await.is_ok());
+        assert!(synth_node.connect(node.addr()).await.is_err());
     }
     // Sleep for some time. This is needed either for:
     // 1. Rippled to connect to the synth node (for Responder side) and reject the handshake,
@@ -534,3 +618,161 @@ async fn run_and_assert_handshake_failure(config: &SynthNodeCfg, connection_side
     synth_node.shut_down().await;
     node.stop().unwrap();
 }
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r005_t1_HANDSHAKE_spoofed_remote_and_local_ip() {
+    // ZG-RESISTANCE-005
+
+    let debug = Debug::disable();
+
+    let gen_cfg =
+        |local_ip: Option<IpAddr>, remote_ip: Option<IpAddr>, extra: Option<&str>| SynthNodeCfg {
+            handshake: Some(HandshakeCfg {
+                http_local_ip: local_ip,
+                http_remote_ip: remote_ip,
+                http_unexpected_extra_field_and_value: extra.map(str::to_owned),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+    let spoofed_ip = Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)));
+
+    // Valid values
+    // The node only checks the addresses of the peers connecting from public addresses, so the
+    // spoofed addresses are accepted over the loopback interface.
+    let cfg = gen_cfg(spoofed_ip, None, None);
+    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    let cfg = gen_cfg(None, spoofed_ip, None);
+    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    let cfg = gen_cfg(Some(IpAddr::V4(Ipv4Addr::LOCALHOST)), spoofed_ip, None);
+    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    // Invalid values
+    // The addresses must still be valid.
+    let cfg = gen_cfg(None, None, Some("Remote-IP: 203.0.113"));
+    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    let cfg = gen_cfg(None, None, Some("Local-IP: localhost"));
+    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+}
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r005_t2_HANDSHAKE_reject_reused_instance_cookie() {
+    // ZG-RESISTANCE-005
+
+    // Build and start the Ripple node.
+    let target = TempDir::new().expect(ERR_TEMPDIR_NEW);
+    let mut node = Node::builder()
+        .start(target.path(), NodeType::Stateless)
+        .await
+        .expect(ERR_NODE_BUILD);
+
+    // Learn the node's instance cookie from its handshake response.
+    let synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
+    synth_node.connect(node.addr()).await.unwrap();
+    let cookie = synth_node
+        .peer_info(node.addr())
+        .and_then(|info| info.instance_cookie)
+        .expect("the node didn't send its instance cookie");
+    synth_node.shut_down().await;
+
+    // Present the node's own cookie to it, which makes the connection look like a connection to
+    // self. The node declines the upgrade before our side sees its cookie.
+    let cfg = SynthNodeCfg {
+        handshake: Some(HandshakeCfg {
+            http_instance_cookie: Some(cookie),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let synth_node = SyntheticNode::new(&cfg).await.unwrap();
+    assert!(matches!(
+        synth_node.connect_with_outcome(node.addr()).await,
+        Ok(HandshakeOutcome::Rejected { .. })
+    ));
+
+    // A different cookie is fine.
+    let cfg = SynthNodeCfg {
+        handshake: Some(HandshakeCfg {
+            http_instance_cookie: Some(cookie.wrapping_add(1).max(1)),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let other_synth_node = SyntheticNode::new(&cfg).await.unwrap();
+    other_synth_node.connect(node.addr()).await.unwrap();
+
+    // Shutdown all nodes.
+    synth_node.shut_down().await;
+    other_synth_node.shut_down().await;
+    assert_eq!(node.stop().expect(ERR_NODE_STOP), ChildExitCode::Success);
+}
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r006_t1_HANDSHAKE_reject_legacy_tls_versions() {
+    // ZG-RESISTANCE-006
+
+    let debug = Debug::disable();
+
+    let gen_cfg = |version: SslVersion| SynthNodeCfg {
+        tls: TlsCfg {
+            min_proto_version: Some(version),
+            max_proto_version: Some(version),
+            // Allow the legacy versions on our side.
+            cipher_list: Some(LEGACY_CIPHER_LIST.to_owned()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    // Valid value
+    let cfg = gen_cfg(SslVersion::TLS1_2);
+    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    // Invalid values
+    let cfg = gen_cfg(SslVersion::TLS1_1);
+    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    let cfg = gen_cfg(SslVersion::TLS1);
+    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+}
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r006_t2_HANDSHAKE_reject_weak_ciphers() {
+    // ZG-RESISTANCE-006
+
+    let debug = Debug::disable();
+
+    // TLS 1.3 cipher suites are all strong, so stick to TLS 1.2 to offer the given ciphers only.
+    let gen_cfg = |ciphers: &str| SynthNodeCfg {
+        tls: TlsCfg {
+            max_proto_version: Some(SslVersion::TLS1_2),
+            cipher_list: Some(format!("{ciphers}:@SECLEVEL=0")),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    // Valid value
+    let cfg = gen_cfg("ECDHE-RSA-AES256-GCM-SHA384");
+    assert!(run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    // Invalid values
+    // Triple DES.
+    let cfg = gen_cfg("DES-CBC3-SHA");
+    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    // No authentication.
+    let cfg = gen_cfg("ADH-AES256-GCM-SHA384");
+    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+
+    // No encryption.
+    let cfg = gen_cfg("NULL-SHA256");
+    assert!(!run_handshake_req_test_with_cfg(cfg, debug).await);
+}
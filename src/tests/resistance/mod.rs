@@ -1,2 +1,3 @@
 mod handshake;
 mod random_bytes;
+mod slowloris;
@@ -27,7 +27,7 @@ async fn r002_node_must_disconnect_when_receiving_random_bytes() {
         .expect("unable to start the node");
 
     for payload in payloads {
-        let synth_node = SyntheticNode::new(&Default::default()).await;
+        let synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
         synth_node.connect(node.addr()).await.unwrap();
         synth_node.unicast_bytes(node.addr(), payload).unwrap();
 
@@ -62,7 +62,7 @@ async fn r004_node_must_disconnect_when_receiving_random_bytes_pre_handshake() {
     };
 
     for payload in payloads {
-        let synth_node = SyntheticNode::new(&cfg).await;
+        let synth_node = SyntheticNode::new(&cfg).await.unwrap();
         synth_node.connect(node.addr()).await.unwrap();
         synth_node.unicast_bytes(node.addr(), payload).unwrap();
 
@@ -0,0 +1,174 @@
+use std::time::{Duration, Instant};
+
+use tempfile::TempDir;
+use ziggurat_core_utils::err_constants::{ERR_NODE_BUILD, ERR_NODE_STOP, ERR_TEMPDIR_NEW};
+
+use crate::{
+    protocol::{
+        codecs::message::Payload,
+        handshake::{HandshakeCfg, Trickle},
+        proto::{tm_ping::PingType, MessageType, TmPing},
+    },
+    setup::node::{ChildExitCode, Node, NodeType},
+    tools::{config::SynthNodeCfg, synth_node::SyntheticNode},
+};
+
+// The time between the trickled bytes; the whole handshake would take minutes to send.
+const TRICKLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// rippled closes the HTTP sessions which don't complete a request in 30 seconds (3 seconds for
+// the loopback addresses), including the TLS handshake.
+const HTTP_SESSION_TIMEOUT: Duration = Duration::from_secs(30);
+
+// rippled pings every peer once per 60 seconds and disconnects the peers which don't answer
+// before the next ping is due.
+const PEER_PING_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+// The allowance for the node's timers.
+const TIMEOUT_MARGIN: Duration = Duration::from_secs(5);
+
+// Attempts a slow handshake with the node and asserts it gives up on it in time, without holding
+// the peer slot open.
+async fn run_and_assert_slow_handshake_rejected(trickle: Trickle) {
+    // Build and start the Ripple node.
+    let target = TempDir::new().expect(ERR_TEMPDIR_NEW);
+    let mut node = Node::builder()
+        .start(target.path(), NodeType::Stateless)
+        .await
+        .expect(ERR_NODE_BUILD);
+
+    let cfg = SynthNodeCfg {
+        handshake: Some(HandshakeCfg {
+            trickle: Some(trickle),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let synth_node = SyntheticNode::new(&cfg).await.unwrap();
+
+    let start = Instant::now();
+    let outcome = synth_node.connect(node.addr()).await;
+    let elapsed = start.elapsed();
+    assert!(
+        outcome.is_err(),
+        "{trickle:?}: the slow handshake was accepted"
+    );
+    assert!(
+        elapsed < HTTP_SESSION_TIMEOUT + TIMEOUT_MARGIN,
+        "{trickle:?}: the node waited for {elapsed:?}"
+    );
+
+    // The node still accepts new peers.
+    let other_synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
+    other_synth_node.connect(node.addr()).await.unwrap();
+
+    // Shutdown all nodes.
+    synth_node.shut_down().await;
+    other_synth_node.shut_down().await;
+    assert_eq!(node.stop().expect(ERR_NODE_STOP), ChildExitCode::Success);
+}
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r007_t1_SLOWLORIS_trickled_tls_client_hello() {
+    // ZG-RESISTANCE-007
+
+    run_and_assert_slow_handshake_rejected(Trickle::Tls {
+        interval: TRICKLE_INTERVAL,
+    })
+    .await;
+}
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r007_t2_SLOWLORIS_trickled_http_headers() {
+    // ZG-RESISTANCE-007
+
+    run_and_assert_slow_handshake_rejected(Trickle::Http {
+        interval: TRICKLE_INTERVAL,
+    })
+    .await;
+}
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r007_t3_SLOWLORIS_stalled_http_headers() {
+    // ZG-RESISTANCE-007
+
+    // Stall within the headers, after the request line.
+    run_and_assert_slow_handshake_rejected(Trickle::StallHttp { sent_bytes: 64 }).await;
+}
+
+#[allow(non_snake_case)]
+#[tokio::test]
+async fn r007_t4_SLOWLORIS_trickled_message() {
+    // ZG-RESISTANCE-007
+
+    // An uncompressed frame header announcing a large ping, whose payload would take hours to
+    // trickle.
+    const PAYLOAD_LEN: u32 = 16 * 1024;
+    let mut frame = PAYLOAD_LEN.to_be_bytes().to_vec();
+    frame.extend_from_slice(&(MessageType::MtPing as u16).to_be_bytes());
+    frame.resize(frame.len() + PAYLOAD_LEN as usize, 0);
+
+    // Build and start the Ripple node.
+    let target = TempDir::new().expect(ERR_TEMPDIR_NEW);
+    let mut node = Node::builder()
+        .start(target.path(), NodeType::Stateless)
+        .await
+        .expect(ERR_NODE_BUILD);
+
+    let synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
+    synth_node.connect(node.addr()).await.unwrap();
+
+    // An idle control peer which keeps answering the node's pings.
+    let mut control_synth_node = SyntheticNode::new(&Default::default()).await.unwrap();
+    control_synth_node.connect(node.addr()).await.unwrap();
+
+    // A pong can't be sent in the middle of the trickled frame, so the trickling peer can't answer
+    // the pings either; the node is expected to drop it, but keep the control peer.
+    let start = Instant::now();
+    let trickled = tokio::select! {
+        result = synth_node.unicast_bytes_trickled(node.addr(), frame, TRICKLE_INTERVAL) => result,
+        _ = answer_pings(&mut control_synth_node) => unreachable!("the pings are answered forever"),
+    };
+    let elapsed = start.elapsed();
+    assert!(trickled.is_err(), "the whole frame was trickled");
+    assert!(
+        elapsed < PEER_PING_TIMEOUT + TIMEOUT_MARGIN,
+        "the node waited for {elapsed:?}"
+    );
+    assert!(!synth_node.is_connected(node.addr()));
+    assert!(
+        control_synth_node.is_connected(node.addr()),
+        "the node dropped the idle control peer as well"
+    );
+
+    // Shutdown all nodes.
+    control_synth_node.shut_down().await;
+    synth_node.shut_down().await;
+    assert_eq!(node.stop().expect(ERR_NODE_STOP), ChildExitCode::Success);
+}
+
+// Answers every ping from the node, never returning.
+async fn answer_pings(synth_node: &mut SyntheticNode) {
+    loop {
+        let (source, message) = synth_node.recv_message().await;
+        if let Payload::TmPing(TmPing {
+            r#type,
+            seq: Some(seq),
+            ..
+        }) = message.payload
+        {
+            if r#type == PingType::PtPing as i32 {
+                let pong = Payload::TmPing(TmPing {
+                    r#type: PingType::PtPong as i32,
+                    seq: Some(seq),
+                    ping_time: None,
+                    net_time: None,
+                });
+                let _ = synth_node.unicast(source, pong);
+            }
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Wire capture of the frames exchanged by synthetic nodes.
+//!
+//! Every frame is recorded as a single JSON line, e.g.:
+//!
+//! > {"timestamp_us":1676543210123456,"direction":"outbound","peer":"127.0.0.1:51235","message_type":3,"raw":"00000004000308001001"}
+//!
+//! The payloads are only rendered into the entries if enabled (see [`Capture::with_payloads`]);
+//! otherwise, the captures can be rendered offline with the `decoder` binary.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    net::SocketAddr,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bytes::BytesMut;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::Span;
+
+use crate::protocol::{
+    codecs::message::{MessageCodec, MessageCodecCfg},
+    render::{to_hex, Render},
+};
+
+/// The direction of a captured frame, from the synthetic node's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// A single captured frame.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureEntry {
+    /// Microseconds since the UNIX epoch.
+    pub timestamp_us: u64,
+    pub direction: Direction,
+    pub peer: SocketAddr,
+    /// Not set for arbitrary bytes sent with
+    /// [`unicast_bytes`](crate::tools::synth_node::SyntheticNode::unicast_bytes).
+    pub message_type: Option<u16>,
+    /// The hex-encoded frame, including the header (see [`to_hex`]).
+    pub raw: String,
+    /// The rendered payload (see [`render`](crate::protocol::render)), if enabled and the frame
+    /// is decodable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Value>,
+}
+
+impl CaptureEntry {
+    /// Returns the decoded raw frame.
+    pub fn raw_bytes(&self) -> Result<Vec<u8>, hex::FromHexError> {
+        hex::decode(&self.raw)
+    }
+}
+
+/// A capture file shared by all the connections of a node.
+///
+/// The entries are buffered and written out once the buffer is full, on [`Capture::flush`] or
+/// when the last handle is dropped (also while unwinding from a failed test).
+#[derive(Clone)]
+pub struct Capture {
+    file: Arc<Mutex<BufWriter<File>>>,
+    // Whether to render the payloads into the entries.
+    payloads: bool,
+}
+
+impl Capture {
+    /// Creates (or truncates) the capture file at the given path.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::create(path)?;
+
+        Ok(Self {
+            file: Arc::new(Mutex::new(BufWriter::new(file))),
+            payloads: false,
+        })
+    }
+
+    /// Renders the decoded payloads into the entries; this decodes every captured frame once
+    /// more, so it's best avoided in high-throughput scenarios.
+    pub fn with_payloads(mut self) -> Self {
+        self.payloads = true;
+        self
+    }
+
+    /// Writes out all the buffered entries.
+    pub fn flush(&self) -> io::Result<()> {
+        self.file.lock().unwrap().flush()
+    }
+
+    /// Returns a handle recording the frames exchanged with the given peer.
+    pub fn for_peer(&self, peer: SocketAddr) -> ConnectionCapture {
+        ConnectionCapture {
+            capture: self.clone(),
+            peer,
+        }
+    }
+
+    fn write(&self, entry: &CaptureEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        self.file.lock().unwrap().write_all(&line)
+    }
+}
+
+/// The capture of a single connection.
+#[derive(Clone)]
+pub struct ConnectionCapture {
+    capture: Capture,
+    peer: SocketAddr,
+}
+
+impl ConnectionCapture {
+    /// Records a single frame.
+    pub fn record(
+        &self,
+        direction: Direction,
+        message_type: Option<u16>,
+        frame: &[u8],
+    ) -> io::Result<()> {
+        let timestamp_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time is before the UNIX epoch")
+            .as_micros() as u64;
+
+        self.capture.write(&CaptureEntry {
+            timestamp_us,
+            direction,
+            peer: self.peer,
+            message_type,
+            raw: to_hex(frame),
+            payload: message_type
+                .filter(|_| self.capture.payloads)
+                .and_then(|_| render_frame(frame)),
+        })
+    }
+}
+
+// Decodes the frame once more, so that the capture is readable without the decoder binary.
+fn render_frame(frame: &[u8]) -> Option<Value> {
+    let mut codec = MessageCodec::new(Span::none(), MessageCodecCfg::default());
+    let message = codec.decode_message(&mut BytesMut::from(frame)).ok()??;
+
+    Some(message.payload.render())
+}
+
+/// Reads all the entries from the capture file at the given path.
+pub fn read_capture<P: AsRef<Path>>(path: P) -> io::Result<Vec<CaptureEntry>> {
+    let reader = BufReader::new(File::open(path)?);
+
+    reader
+        .lines()
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn record_and_read() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("capture.jsonl");
+        let peer = "127.0.0.1:51235".parse().unwrap();
+
+        let capture = Capture::create(&path).unwrap().with_payloads();
+        let connection = capture.for_peer(peer);
+        connection
+            .record(
+                Direction::Outbound,
+                Some(3),
+                b"\x00\x00\x00\x02\x00\x03\x08\x00",
+            )
+            .unwrap();
+        connection
+            .record(Direction::Inbound, None, b"garbage")
+            .unwrap();
+        capture.flush().unwrap();
+
+        let entries = read_capture(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].direction, Direction::Outbound);
+        assert_eq!(entries[0].peer, peer);
+        assert_eq!(entries[0].message_type, Some(3));
+        assert_eq!(entries[0].raw, "0000000200030800");
+        assert_eq!(
+            entries[0].payload,
+            Some(serde_json::json!({ "TmPing": { "type": "PtPing" } }))
+        );
+
+        assert_eq!(entries[1].direction, Direction::Inbound);
+        assert_eq!(entries[1].message_type, None);
+        assert_eq!(entries[1].raw_bytes().unwrap(), b"garbage");
+        assert_eq!(entries[1].payload, None);
+        assert!(entries[1].timestamp_us >= entries[0].timestamp_us);
+
+        // By default, the payloads aren't rendered; the frames are hex-encoded like the binary
+        // data of the rendered payloads.
+        let capture = Capture::create(&path).unwrap();
+        capture
+            .for_peer(peer)
+            .record(
+                Direction::Inbound,
+                Some(255),
+                b"\x00\x00\x00\x02\x00\xff\xab\xcd",
+            )
+            .unwrap();
+        capture.flush().unwrap();
+
+        let entries = read_capture(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].raw, "0000000200FFABCD");
+        assert_eq!(entries[0].payload, None);
+    }
+}
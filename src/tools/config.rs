@@ -1,19 +1,55 @@
-use std::net::{IpAddr, Ipv4Addr};
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+};
 
-use crate::protocol::handshake::HandshakeCfg;
+use serde::Deserialize;
+
+use crate::{
+    protocol::{codecs::message::MessageCodecCfg, handshake::HandshakeCfg},
+    tools::inner_node::{KeyType, TlsCfg},
+};
 
 /// Synthetic Node Configuration.
-#[derive(Clone)]
+///
+/// It can be loaded from TOML, where the unset fields keep their default values; the TLS and
+/// Pea2Pea configurations can only be set in code.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
 pub struct SynthNodeCfg {
     /// Whether or not to generate new keys for a handshake.
     pub generate_new_keys: bool,
 
+    /// The type of the node identity keys, used to sign the handshake.
+    pub key_type: KeyType,
+
     /// Handshake configuration.
     ///
     /// If not set, the handshake will be skipped.
     pub handshake: Option<HandshakeCfg>,
 
+    /// TLS configuration, applied to every connection.
+    #[serde(skip)]
+    pub tls: TlsCfg,
+
+    /// Message codec configuration, applied to every connection.
+    pub codec: MessageCodecCfg,
+
+    /// If set, every frame sent and received is recorded to the file at the given path.
+    ///
+    /// See [`capture`](crate::tools::capture) for the file format.
+    pub capture: Option<PathBuf>,
+
+    /// Whether to render the decoded payloads into the capture (see
+    /// [`Capture::with_payloads`](crate::tools::capture::Capture::with_payloads)).
+    pub capture_payloads: bool,
+
+    /// Whether to keep the message statistics of every connection (see
+    /// [`stats`](crate::tools::stats)); off by default, as it adds some overhead to every frame.
+    pub stats: bool,
+
     /// Pea2Pea configuration.
+    #[serde(skip)]
     pub pea2pea_config: pea2pea::Config,
 }
 
@@ -22,7 +58,13 @@ impl Default for SynthNodeCfg {
         let ip_addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
         Self {
             generate_new_keys: true,
+            key_type: KeyType::Secp256k1,
             handshake: Some(Default::default()),
+            tls: Default::default(),
+            codec: Default::default(),
+            capture: None,
+            capture_payloads: false,
+            stats: false,
             pea2pea_config: pea2pea::Config {
                 listener_ip: Some(ip_addr),
                 ..Default::default()
@@ -11,7 +11,7 @@ use rand::Rng;
 use reqwest::Client;
 use tokio::time::sleep;
 use tracing::{debug, trace, warn};
-use ziggurat_xrpl::tools::inner_node::InnerNode;
+use ziggurat_xrpl::{protocol::handshake::HandshakeOutcome, tools::inner_node::InnerNode};
 
 use crate::{
     crawl::{get_crawl_response, CrawlResponse, Peer},
@@ -65,6 +65,8 @@ pub(super) fn crawl(
 
                     // TODO(team): decide how to use this information about the handshake_successful data
                     tokio::spawn(try_handshake(
+                        client.clone(),
+                        limiter.clone(),
                         SocketAddr::new(ip, *port),
                         known_network.clone(),
                     ));
@@ -114,12 +116,24 @@ fn get_ports_to_try(from_response: Option<u16>) -> HashSet<u16> {
     ports
 }
 
-async fn try_handshake(addr: SocketAddr, known_network: Arc<KnownNetwork>) {
+async fn try_handshake(
+    client: Client,
+    limiter: Arc<Limiter>,
+    addr: SocketAddr,
+    known_network: Arc<KnownNetwork>,
+) {
     let (sender, _receiver) = tokio::sync::mpsc::channel(1024);
-    let node = InnerNode::new(&Default::default(), sender).await;
+    let node = match InnerNode::new(&Default::default(), sender).await {
+        Ok(node) => node,
+        Err(e) => {
+            warn!("Unable to create a node for the handshake with {addr}: {e}");
+            return;
+        }
+    };
     node.enable_handshake().await;
 
-    let result = node.connect(addr).await.is_ok();
+    let outcome = node.connect_with_outcome(addr).await;
+    let result = matches!(outcome, Ok(HandshakeOutcome::Accepted));
     known_network.set_handshake_successful(addr, result).await;
     if result {
         trace!("Successful handshake to {}", addr);
@@ -127,6 +141,20 @@ async fn try_handshake(addr: SocketAddr, known_network: Arc<KnownNetwork>) {
         trace!("Unsuccessful handshake to {}", addr);
     }
     node.shut_down().await;
+
+    // A node without free slots suggests other peers to connect to.
+    if let Ok(HandshakeOutcome::Rejected { redirect_peers, .. }) = outcome {
+        for peer in redirect_peers {
+            crawl(
+                client.clone(),
+                limiter.clone(),
+                peer.ip(),
+                Some(peer.port()),
+                known_network.clone(),
+            )
+            .await;
+        }
+    }
 }
 
 async fn try_crawling(
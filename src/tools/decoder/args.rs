@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub(super) enum InputFormat {
+    /// Hex-encoded frames
+    Hex,
+    /// Base64-encoded frames
+    Base64,
+    /// A wire capture file recorded by a synthetic node
+    Capture,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub(super) enum OutputFormat {
+    /// Indented, human-readable text
+    Human,
+    /// A JSON object per line
+    Json,
+}
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about, long_about = None)]
+pub(super) struct Args {
+    /// The file to read the frames from; the standard input is used if not present
+    #[clap(value_parser)]
+    pub(super) input: Option<PathBuf>,
+
+    /// The format of the input
+    #[clap(short, long, value_enum, default_value_t = InputFormat::Hex)]
+    pub(super) input_format: InputFormat,
+
+    /// The format of the output
+    #[clap(short, long, value_enum, default_value_t = OutputFormat::Human)]
+    pub(super) output: OutputFormat,
+}
@@ -0,0 +1,195 @@
+//! An offline decoder of the peer protocol frames.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    net::SocketAddr,
+};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::BytesMut;
+use clap::Parser;
+use serde_json::{Map, Value};
+use tracing::Span;
+use ziggurat_xrpl::{
+    protocol::{
+        codecs::message::{MessageCodec, MessageCodecCfg},
+        render::{to_hex, to_text, Render},
+    },
+    tools::capture::{read_capture, CaptureEntry, Direction},
+};
+
+use crate::args::{Args, InputFormat, OutputFormat};
+
+mod args;
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let frames = match args.input_format {
+        InputFormat::Hex => decode_frames(&hex::decode(read_input(&args)?)?),
+        InputFormat::Base64 => decode_frames(&STANDARD.decode(read_input(&args)?)?),
+        InputFormat::Capture => decode_capture(&args)?,
+    };
+
+    for frame in frames {
+        let frame = Value::Object(frame);
+        match args.output {
+            OutputFormat::Human => println!("{}", to_text(&frame)),
+            OutputFormat::Json => println!("{frame}"),
+        }
+    }
+
+    Ok(())
+}
+
+// Reads the encoded input, ignoring any whitespace.
+fn read_input(args: &Args) -> anyhow::Result<String> {
+    let input = match &args.input {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("unable to read {}", path.display()))?,
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            input
+        }
+    };
+
+    Ok(input.split_whitespace().collect())
+}
+
+// The unframed entries of a peer and direction (see `SyntheticNode::unicast_bytes`), which are
+// joined until they form whole frames, e.g. when a message is trickled a byte at a time.
+struct Unframed {
+    // The entry the pending bytes start in.
+    entry: CaptureEntry,
+    bytes: BytesMut,
+}
+
+// Decodes the frames of every capture entry, prefixed with the details of the entry they start in.
+fn decode_capture(args: &Args) -> anyhow::Result<Vec<Map<String, Value>>> {
+    let path = args
+        .input
+        .as_ref()
+        .context("the capture input has to be read from a file")?;
+    let entries =
+        read_capture(path).with_context(|| format!("unable to read {}", path.display()))?;
+
+    let mut frames = Vec::new();
+    let mut unframed: HashMap<(SocketAddr, Direction), Unframed> = HashMap::new();
+    for entry in entries {
+        let bytes = entry
+            .raw_bytes()
+            .with_context(|| format!("invalid capture entry: {}", entry.raw))?;
+        let key = (entry.peer, entry.direction);
+
+        // Every framed entry holds a single whole frame, which also ends any partial frame sent
+        // before it.
+        if entry.message_type.is_some() {
+            if let Some(partial) = unframed.remove(&key) {
+                frames.push(with_details(&partial.entry, partial_frame(&partial.bytes))?);
+            }
+            for frame in decode_frames(&bytes) {
+                frames.push(with_details(&entry, frame)?);
+            }
+            continue;
+        }
+
+        let pending = unframed.entry(key).or_insert_with(|| Unframed {
+            entry: entry.clone(),
+            bytes: BytesMut::new(),
+        });
+        pending.bytes.extend_from_slice(&bytes);
+
+        let decoded = decode_whole_frames(&mut pending.bytes);
+        let decoded_any = !decoded.is_empty();
+        for frame in decoded {
+            frames.push(with_details(&pending.entry, frame)?);
+        }
+        if pending.bytes.is_empty() {
+            unframed.remove(&key);
+        } else if decoded_any {
+            // The remaining bytes belong to the current entry.
+            pending.entry = entry;
+        }
+    }
+
+    // The bytes which never formed a whole frame.
+    let mut partials: Vec<_> = unframed.into_values().collect();
+    partials.sort_by_key(|partial| partial.entry.timestamp_us);
+    for partial in partials {
+        frames.push(with_details(&partial.entry, partial_frame(&partial.bytes))?);
+    }
+
+    Ok(frames)
+}
+
+fn with_details(
+    entry: &CaptureEntry,
+    frame: Map<String, Value>,
+) -> anyhow::Result<Map<String, Value>> {
+    let mut with_details = Map::new();
+    with_details.insert("timestamp_us".into(), entry.timestamp_us.into());
+    with_details.insert("direction".into(), serde_json::to_value(entry.direction)?);
+    with_details.insert("peer".into(), entry.peer.to_string().into());
+    with_details.extend(frame);
+
+    Ok(with_details)
+}
+
+// Decodes all the frames in the buffer; the ones which can't be decoded are rendered as errors.
+fn decode_frames(bytes: &[u8]) -> Vec<Map<String, Value>> {
+    let mut src = BytesMut::from(bytes);
+    let mut frames = decode_whole_frames(&mut src);
+
+    if !src.is_empty() {
+        let error = format!("incomplete frame, {} trailing bytes", src.len());
+        frames.push(error_frame(error));
+    }
+
+    frames
+}
+
+// Decodes the whole frames at the start of the buffer, leaving only an incomplete frame in it.
+fn decode_whole_frames(src: &mut BytesMut) -> Vec<Map<String, Value>> {
+    let mut codec = MessageCodec::new(Span::none(), MessageCodecCfg::default());
+    let mut frames = Vec::new();
+
+    loop {
+        match codec.decode_raw_message(src) {
+            Ok(Some(message)) => {
+                let mut frame = Map::new();
+                frame.insert("header".into(), message.header.render());
+                match message.decode() {
+                    Ok(payload) => frame.insert("payload".into(), payload.render()),
+                    Err(e) => frame.insert("error".into(), e.to_string().into()),
+                };
+                frames.push(frame);
+            }
+            Ok(None) => break,
+            // The frame boundaries are lost, so the rest of the input can't be decoded.
+            Err(e) => {
+                frames.push(error_frame(e.to_string()));
+                src.clear();
+                break;
+            }
+        }
+    }
+
+    frames
+}
+
+// The start of a frame whose remaining bytes were never sent.
+fn partial_frame(bytes: &[u8]) -> Map<String, Value> {
+    let mut frame = Map::new();
+    frame.insert("partial".into(), to_hex(bytes).into());
+    frame
+}
+
+fn error_frame(error: String) -> Map<String, Value> {
+    let mut frame = Map::new();
+    frame.insert("error".into(), error.into());
+    frame
+}
@@ -1,31 +1,52 @@
 use std::{
+    collections::HashMap,
     io,
     net::{IpAddr, SocketAddr},
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
-use openssl::ssl::{SslAcceptor, SslConnector, SslMethod, SslVerifyMode};
-use pea2pea::{Node, Pea2Pea};
-use secp256k1::{
-    constants::{PUBLIC_KEY_SIZE, SECRET_KEY_SIZE},
-    PublicKey, Secp256k1, SecretKey,
+use ed25519_dalek::Signer;
+use openssl::{
+    error::ErrorStack,
+    ssl::{
+        SslAcceptor, SslConnector, SslContextBuilder, SslMethod, SslOptions, SslVerifyMode,
+        SslVersion,
+    },
 };
+use pea2pea::{Node, Pea2Pea};
+use rand::{thread_rng, Rng};
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha512};
 use tokio::{net::TcpSocket, sync::mpsc::Sender};
+use tracing::{debug, error};
 
 use crate::{
-    protocol::{codecs::message::BinaryMessage, handshake::HandshakeCfg},
+    protocol::{
+        codecs::message::{MessageCodec, MessageCodecCfg},
+        features::ProtocolFeatures,
+        handshake::{decode_base58, HandshakeCfg, HandshakeOutcome, NodeType, PeerInfo},
+        reading::InboundMessage,
+    },
     setup::constants::{SYNTHETIC_NODE_PRIVATE_KEY, SYNTHETIC_NODE_PUBLIC_KEY},
-    tools::{config::SynthNodeCfg, tls_cert},
+    tools::{capture::Capture, config::SynthNodeCfg, stats::Stats, tls_cert::TlsIdentity},
 };
 
 // A synthetic node adhering to Ripple's network protocol.
 #[derive(Clone)]
 pub struct InnerNode {
     node: Node,
-    pub(crate) sender: Sender<(SocketAddr, BinaryMessage)>,
+    pub(crate) sender: Sender<(SocketAddr, InboundMessage)>,
     pub crypto: Arc<Crypto>,
     pub tls: Tls,
     pub handshake_cfg: Option<HandshakeCfg>,
+    pub codec_cfg: MessageCodecCfg,
+    pub capture: Option<Capture>,
+    pub stats: Option<Stats>,
+    // The information presented by the peers during the handshake, kept until they disconnect.
+    peer_info: Arc<Mutex<HashMap<SocketAddr, PeerInfo>>>,
+    // The rejections of the handshakes initiated by the node, kept until the connection attempt returns.
+    handshake_rejections: Arc<Mutex<HashMap<SocketAddr, HandshakeOutcome>>>,
 }
 
 // An object containing TLS handlers.
@@ -35,11 +56,196 @@ pub struct Tls {
     pub connector: SslConnector,
 }
 
+impl Tls {
+    /// Creates the TLS handlers configured as given.
+    pub fn new(cfg: &TlsCfg) -> Result<Self, ErrorStack> {
+        let identity = cfg.identity.clone().unwrap_or_else(TlsIdentity::shared);
+
+        // TLS acceptor
+        let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+        acceptor.set_private_key(&identity.key_pair)?;
+        acceptor.set_certificate(&identity.cert)?;
+        cfg.apply(&mut acceptor)?;
+
+        // TLS connector
+        let mut connector = SslConnector::builder(SslMethod::tls())?;
+        connector.set_verify(SslVerifyMode::NONE); // we might remove it once the keypair is solid
+        cfg.apply(&mut connector)?;
+
+        Ok(Self {
+            acceptor: acceptor.build(),
+            connector: connector.build(),
+        })
+    }
+}
+
+/// TLS configuration, applied to both the inbound and the outbound connections.
+///
+/// The unset options are left at the OpenSSL defaults. Note that OpenSSL refuses the protocol
+/// versions and ciphers below its security level, which can be lowered in the cipher list, e.g.
+/// `DEFAULT:@SECLEVEL=0`.
+#[derive(Clone)]
+pub struct TlsCfg {
+    /// The certificate and private key presented to the peers; if not set, the identity shared
+    /// by all synthetic nodes is used.
+    pub identity: Option<TlsIdentity>,
+
+    /// The lowest protocol version offered or accepted.
+    pub min_proto_version: Option<SslVersion>,
+
+    /// The highest protocol version offered or accepted.
+    pub max_proto_version: Option<SslVersion>,
+
+    /// The ciphers available up to TLS 1.2, in the OpenSSL cipher list format.
+    pub cipher_list: Option<String>,
+
+    /// The TLS 1.3 cipher suites, in the OpenSSL cipher suites format.
+    pub ciphersuites: Option<String>,
+
+    /// Whether the session may be renegotiated (up to TLS 1.2).
+    pub renegotiation: bool,
+
+    /// Whether session tickets are issued and used for the session resumption.
+    pub session_tickets: bool,
+}
+
+impl Default for TlsCfg {
+    fn default() -> Self {
+        Self {
+            identity: None,
+            min_proto_version: None,
+            max_proto_version: None,
+            cipher_list: None,
+            ciphersuites: None,
+            renegotiation: true,
+            session_tickets: true,
+        }
+    }
+}
+
+impl TlsCfg {
+    // Applies the configuration to the acceptor or connector being built.
+    fn apply(&self, builder: &mut SslContextBuilder) -> Result<(), ErrorStack> {
+        if let Some(version) = self.min_proto_version {
+            builder.set_min_proto_version(Some(version))?;
+        }
+        if let Some(version) = self.max_proto_version {
+            builder.set_max_proto_version(Some(version))?;
+        }
+        if let Some(ref cipher_list) = self.cipher_list {
+            builder.set_cipher_list(cipher_list)?;
+        }
+        if let Some(ref ciphersuites) = self.ciphersuites {
+            builder.set_ciphersuites(ciphersuites)?;
+        }
+        if !self.renegotiation {
+            builder.set_options(SslOptions::NO_RENEGOTIATION);
+        }
+        if !self.session_tickets {
+            builder.set_options(SslOptions::NO_TICKET);
+        }
+
+        Ok(())
+    }
+}
+
+/// The prefix distinguishing serialized ed25519 public keys from secp256k1 ones.
+pub const ED25519_KEY_PREFIX: u8 = 0xED;
+
+/// The type of the node identity keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyType {
+    #[default]
+    Secp256k1,
+    Ed25519,
+}
+
+/// The node identity keypair.
+pub enum Keys {
+    Secp256k1 {
+        private_key: SecretKey,
+        public_key: PublicKey,
+    },
+    Ed25519(ed25519_dalek::Keypair),
+}
+
 // An object dedicated to cryptographic functionalities.
 pub struct Crypto {
     pub engine: Secp256k1<secp256k1::All>,
-    pub private_key: SecretKey,
-    pub public_key: PublicKey,
+    pub keys: Keys,
+}
+
+impl Crypto {
+    /// Creates the node identity keys of the given type; unless new keys are generated, the
+    /// predefined private key is used (as the seed of the ed25519 keys).
+    pub fn new(key_type: KeyType, generate_new_keys: bool) -> Self {
+        let engine = Secp256k1::new();
+
+        let keys = match (key_type, generate_new_keys) {
+            (KeyType::Secp256k1, true) => {
+                let (private_key, public_key) =
+                    engine.generate_keypair(&mut secp256k1::rand::thread_rng());
+                Keys::Secp256k1 {
+                    private_key,
+                    public_key,
+                }
+            }
+            (KeyType::Secp256k1, false) => {
+                let (private_key, public_key) =
+                    decode_predefined_keys().expect("invalid predefined keys");
+                Keys::Secp256k1 {
+                    private_key,
+                    public_key,
+                }
+            }
+            (KeyType::Ed25519, true) => Keys::Ed25519(ed25519_keypair(&thread_rng().gen())),
+            (KeyType::Ed25519, false) => {
+                let (private_key, _) = decode_predefined_keys().expect("invalid predefined keys");
+                Keys::Ed25519(ed25519_keypair(&private_key.secret_bytes()))
+            }
+        };
+
+        Self { engine, keys }
+    }
+
+    /// Returns the serialized public key; ed25519 keys are prefixed with [`ED25519_KEY_PREFIX`].
+    pub fn public_key(&self) -> Vec<u8> {
+        match self.keys {
+            Keys::Secp256k1 { public_key, .. } => public_key.serialize().to_vec(),
+            Keys::Ed25519(ref keypair) => {
+                let mut public_key = vec![ED25519_KEY_PREFIX];
+                public_key.extend_from_slice(keypair.public.as_bytes());
+                public_key
+            }
+        }
+    }
+
+    /// Signs a 32-byte digest, like the session signature; the signature is DER-encoded for
+    /// secp256k1 keys, while ed25519 keys sign the digest as a plain message.
+    pub fn sign_digest(&self, digest: &[u8]) -> Vec<u8> {
+        match self.keys {
+            Keys::Secp256k1 {
+                ref private_key, ..
+            } => {
+                let message = Message::from_slice(digest).expect("the digest is 32 bytes long");
+                self.engine
+                    .sign_ecdsa(&message, private_key)
+                    .serialize_der()
+                    .to_vec()
+            }
+            Keys::Ed25519(ref keypair) => keypair.sign(digest).to_bytes().to_vec(),
+        }
+    }
+
+    /// Signs an arbitrary message, like a manifest; like in rippled, secp256k1 keys sign the
+    /// first half of its SHA-512 hash.
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        match self.keys {
+            Keys::Secp256k1 { .. } => self.sign_digest(&Sha512::digest(message)[..32]),
+            Keys::Ed25519(ref keypair) => keypair.sign(message).to_bytes().to_vec(),
+        }
+    }
 }
 
 impl Pea2Pea for InnerNode {
@@ -49,47 +255,106 @@ impl Pea2Pea for InnerNode {
 }
 
 impl InnerNode {
-    pub async fn new(cfg: &SynthNodeCfg, sender: Sender<(SocketAddr, BinaryMessage)>) -> Self {
+    pub async fn new(
+        cfg: &SynthNodeCfg,
+        sender: Sender<(SocketAddr, InboundMessage)>,
+    ) -> io::Result<Self> {
         // generate the keypair and prepare the crypto engine
 
-        let engine = Secp256k1::new();
-        let (private_key, public_key) = if cfg.generate_new_keys {
-            engine.generate_keypair(&mut secp256k1::rand::thread_rng())
-        } else {
-            decode_predefined_keys().expect("invalid predefined keys")
-        };
-        let crypto = Arc::new(Crypto {
-            engine,
-            private_key,
-            public_key,
-        });
-
-        // TLS acceptor
+        let crypto = Arc::new(Crypto::new(cfg.key_type, cfg.generate_new_keys));
 
-        let (_ca_cert, ca_key_pair) = tls_cert::mk_ca_cert().unwrap();
-        let (cert, key_pair) = tls_cert::mk_ca_signed_cert(&ca_key_pair).unwrap();
+        // TLS acceptor and connector
 
-        let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
-        acceptor.set_private_key(&key_pair).unwrap();
-        acceptor.set_certificate(&cert).unwrap();
-        let acceptor = acceptor.build();
+        let tls = Tls::new(&cfg.tls)?;
 
-        // TLS connector
-        let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
-        connector.set_verify(SslVerifyMode::NONE); // we might remove it once the keypair is solid
-        let connector = connector.build();
+        // wire capture
+        let capture = match cfg.capture {
+            Some(ref path) if cfg.capture_payloads => Some(Capture::create(path)?.with_payloads()),
+            Some(ref path) => Some(Capture::create(path)?),
+            None => None,
+        };
 
         // the node
-        Self {
+        Ok(Self {
             node: Node::new(cfg.pea2pea_config.clone()),
             sender,
             crypto,
-            tls: Tls {
-                acceptor,
-                connector,
-            },
+            tls,
             handshake_cfg: cfg.handshake.clone(),
+            codec_cfg: cfg.codec.clone(),
+            capture,
+            stats: cfg.stats.then(Default::default),
+            peer_info: Default::default(),
+            handshake_rejections: Default::default(),
+        })
+    }
+
+    /// Returns the information presented by the given address during the handshake.
+    pub fn peer_info(&self, addr: SocketAddr) -> Option<PeerInfo> {
+        self.peer_info.lock().unwrap().get(&addr).cloned()
+    }
+
+    /// Returns the protocol features negotiated with the given address during the handshake.
+    pub fn negotiated_features(&self, addr: SocketAddr) -> Option<ProtocolFeatures> {
+        self.peer_info
+            .lock()
+            .unwrap()
+            .get(&addr)
+            .map(|info| info.features)
+    }
+
+    pub(crate) fn set_peer_info(&self, addr: SocketAddr, info: PeerInfo) {
+        debug!(parent: self.node().span(), "verified {addr} as {}, negotiated features: {:?}", info.public_key_base58(), info.features);
+        self.peer_info.lock().unwrap().insert(addr, info);
+    }
+
+    pub(crate) fn set_handshake_rejection(&self, addr: SocketAddr, outcome: HandshakeOutcome) {
+        self.handshake_rejections
+            .lock()
+            .unwrap()
+            .insert(addr, outcome);
+    }
+
+    // Forgets the handshake details of the given address once its connection is closed.
+    pub(crate) fn clear_peer_state(&self, addr: SocketAddr) {
+        self.peer_info.lock().unwrap().remove(&addr);
+        self.handshake_rejections.lock().unwrap().remove(&addr);
+    }
+
+    // Turns the result of a connection attempt into the handshake outcome; only the handshakes
+    // declined by the peer are rejections, other failures remain errors.
+    fn handshake_outcome(
+        &self,
+        target: SocketAddr,
+        result: io::Result<()>,
+    ) -> io::Result<HandshakeOutcome> {
+        let rejection = self.handshake_rejections.lock().unwrap().remove(&target);
+
+        match (result, rejection) {
+            (Ok(()), _) => Ok(HandshakeOutcome::Accepted),
+            (Err(_), Some(rejection)) => Ok(rejection),
+            (Err(e), None) => Err(e),
+        }
+    }
+
+    /// Returns a message codec for the connection with the given address.
+    ///
+    /// Compression is enabled if it was negotiated during the handshake.
+    pub fn message_codec(&self, addr: SocketAddr) -> MessageCodec {
+        let mut cfg = self.codec_cfg.clone();
+        if let Some(features) = self.negotiated_features(addr) {
+            cfg.compression |= features.compression;
+        }
+
+        let mut codec = MessageCodec::new(self.node().span().clone(), cfg);
+        if let Some(ref stats) = self.stats {
+            codec = codec.with_stats(stats.for_peer(addr));
+        }
+        if let Some(ref capture) = self.capture {
+            codec = codec.with_capture(capture.for_peer(addr));
         }
+
+        codec
     }
 
     pub fn is_connected_ip(&self, ip: IpAddr) -> bool {
@@ -101,40 +366,61 @@ impl InnerNode {
 
     /// Connects to the target address.
     pub async fn connect(&self, target: SocketAddr) -> io::Result<()> {
-        self.node.connect(target).await?;
-        Ok(())
+        let result = self.node.connect(target).await;
+        self.handshake_rejections.lock().unwrap().remove(&target);
+        result
     }
 
     /// Connects to the target address.
     pub async fn connect_from(&self, target: SocketAddr, socket: TcpSocket) -> io::Result<()> {
-        self.node.connect_using_socket(target, socket).await
+        let result = self.node.connect_using_socket(target, socket).await;
+        self.handshake_rejections.lock().unwrap().remove(&target);
+        result
+    }
+
+    /// Connects to the target address, reporting the handshake outcome.
+    pub async fn connect_with_outcome(&self, target: SocketAddr) -> io::Result<HandshakeOutcome> {
+        let result = self.node.connect(target).await;
+        self.handshake_outcome(target, result)
+    }
+
+    /// Connects to the target address using specified socket, reporting the handshake outcome.
+    pub async fn connect_from_with_outcome(
+        &self,
+        target: SocketAddr,
+        socket: TcpSocket,
+    ) -> io::Result<HandshakeOutcome> {
+        let result = self.node.connect_using_socket(target, socket).await;
+        self.handshake_outcome(target, result)
     }
 
     /// Gracefully shuts down the node.
     pub async fn shut_down(&self) {
-        self.node.shut_down().await
-    }
-}
+        self.node.shut_down().await;
 
-fn decode_to_vec(base58str: &str, size: usize) -> bs58::decode::Result<Vec<u8>> {
-    let mut bytes = bs58::decode(base58str)
-        .with_alphabet(bs58::Alphabet::RIPPLE)
-        .into_vec()?;
-    // Remove the first byte as it's an extra byte added before serialization to distinguish
-    // hashes of different things (i.e. public/private keys, accounts, transactions and so on).
-    bytes.remove(0);
-    bytes.truncate(size);
-    Ok(bytes)
+        if let Some(ref capture) = self.capture {
+            if let Err(e) = capture.flush() {
+                error!(parent: self.node().span(), "unable to write out the capture: {e}");
+            }
+        }
+    }
 }
 
 fn decode_predefined_keys() -> Result<(SecretKey, PublicKey), secp256k1::Error> {
-    let bytes = decode_to_vec(SYNTHETIC_NODE_PRIVATE_KEY, SECRET_KEY_SIZE)
+    let bytes = decode_base58(NodeType::Private, SYNTHETIC_NODE_PRIVATE_KEY)
         .expect("unable to decode the private key");
     let private_key = SecretKey::from_slice(bytes.as_slice())?;
 
-    let bytes = decode_to_vec(SYNTHETIC_NODE_PUBLIC_KEY, PUBLIC_KEY_SIZE)
+    let bytes = decode_base58(NodeType::Public, SYNTHETIC_NODE_PUBLIC_KEY)
         .expect("unable to decode the public key");
     let public_key = PublicKey::from_slice(bytes.as_slice())?;
 
     Ok((private_key, public_key))
 }
+
+fn ed25519_keypair(seed: &[u8; 32]) -> ed25519_dalek::Keypair {
+    let secret = ed25519_dalek::SecretKey::from_bytes(seed).expect("the seed is 32 bytes long");
+    let public = ed25519_dalek::PublicKey::from(&secret);
+
+    ed25519_dalek::Keypair { secret, public }
+}
@@ -1,12 +1,16 @@
 //! Utilities for network testing.
 
+pub mod capture;
 pub mod config;
 pub mod constants;
 pub mod inner_node;
 pub mod ips;
+pub mod replay;
 pub mod rpc;
+pub mod stats;
 pub mod synth_node;
 pub mod tls_cert;
+pub mod trickle;
 
 /// Waits until an expression is true or times out.
 ///
@@ -0,0 +1,351 @@
+//! Replay of recorded sessions (see [`capture`](crate::tools::capture)) against a node.
+
+use std::{
+    collections::BTreeMap,
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use tokio::time::timeout;
+
+use crate::tools::{
+    capture::{CaptureEntry, Direction},
+    synth_node::SyntheticNode,
+};
+
+/// The largest accepted [`ReplayCfg::time_scale`]; it keeps every scaled delay (even one of the
+/// largest recordable `u64::MAX` microseconds) within the range of a [`Duration`].
+pub const MAX_TIME_SCALE: f64 = 1_000.0;
+
+/// Replay configuration.
+#[derive(Clone, Debug)]
+pub struct ReplayCfg {
+    /// The factor applied to the recorded delays between the outbound frames.
+    ///
+    /// `1.0` keeps the original timing, `0.5` replays twice as fast and `0.0` sends all the
+    /// frames without any delay; it can't exceed [`MAX_TIME_SCALE`].
+    pub time_scale: f64,
+
+    /// How long to keep collecting inbound messages after the last frame was sent.
+    pub settle_time: Duration,
+}
+
+impl Default for ReplayCfg {
+    fn default() -> Self {
+        Self {
+            time_scale: 1.0,
+            settle_time: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ReplayCfg {
+    /// Checks that the time scale is a non-negative number no larger than [`MAX_TIME_SCALE`].
+    pub fn validate(&self) -> io::Result<()> {
+        if !(0.0..=MAX_TIME_SCALE).contains(&self.time_scale) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid time scale: {}", self.time_scale),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// The difference in the number of inbound messages of a single type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageTypeDiff {
+    pub message_type: u16,
+    pub recorded: usize,
+    pub replayed: usize,
+}
+
+/// The outcome of a replay.
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    /// The number of frames sent to the target.
+    pub frames_sent: usize,
+    /// The inbound message types, in the order they were recorded.
+    pub recorded_inbound: Vec<u16>,
+    /// The inbound message types, in the order they were received during the replay.
+    pub replayed_inbound: Vec<u16>,
+}
+
+impl ReplayReport {
+    /// Returns the message types which were received a different number of times than recorded.
+    pub fn diff(&self) -> Vec<MessageTypeDiff> {
+        let mut counts: BTreeMap<u16, (usize, usize)> = BTreeMap::new();
+        for message_type in &self.recorded_inbound {
+            counts.entry(*message_type).or_default().0 += 1;
+        }
+        for message_type in &self.replayed_inbound {
+            counts.entry(*message_type).or_default().1 += 1;
+        }
+
+        counts
+            .into_iter()
+            .filter(|(_, (recorded, replayed))| recorded != replayed)
+            .map(|(message_type, (recorded, replayed))| MessageTypeDiff {
+                message_type,
+                recorded,
+                replayed,
+            })
+            .collect()
+    }
+
+    /// Returns `true` if the same inbound message types were received as recorded.
+    pub fn is_match(&self) -> bool {
+        self.diff().is_empty()
+    }
+}
+
+/// Re-sends the outbound frames of the recorded session to the target, collecting the inbound
+/// messages in the meantime.
+///
+/// The synthetic node has to be connected to the target already; the entries are expected to
+/// belong to a single recorded connection (see [`entries_for_peer`]). Nothing is sent if the
+/// configuration is invalid (see [`ReplayCfg::validate`]).
+pub async fn replay(
+    synth_node: &mut SyntheticNode,
+    target: SocketAddr,
+    entries: &[CaptureEntry],
+    cfg: &ReplayCfg,
+) -> io::Result<ReplayReport> {
+    cfg.validate()?;
+
+    let mut report = ReplayReport {
+        recorded_inbound: entries
+            .iter()
+            .filter(|entry| entry.direction == Direction::Inbound)
+            .filter_map(|entry| entry.message_type)
+            .collect(),
+        ..Default::default()
+    };
+
+    let mut previous_timestamp = None;
+    for entry in entries
+        .iter()
+        .filter(|entry| entry.direction == Direction::Outbound)
+    {
+        let frame = entry
+            .raw_bytes()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        // Keep the (scaled) recorded delay between frames.
+        let delay = previous_timestamp
+            .map(|previous| entry.timestamp_us.saturating_sub(previous))
+            .map(|delay_us| Duration::from_micros(delay_us).mul_f64(cfg.time_scale))
+            .unwrap_or_default();
+        previous_timestamp = Some(entry.timestamp_us);
+        collect_inbound(synth_node, target, delay, &mut report.replayed_inbound).await;
+
+        synth_node.unicast_bytes(target, frame)?;
+        report.frames_sent += 1;
+    }
+
+    collect_inbound(
+        synth_node,
+        target,
+        cfg.settle_time,
+        &mut report.replayed_inbound,
+    )
+    .await;
+
+    Ok(report)
+}
+
+/// Returns the entries recorded for the connection with the given peer.
+pub fn entries_for_peer(entries: &[CaptureEntry], peer: SocketAddr) -> Vec<CaptureEntry> {
+    entries
+        .iter()
+        .filter(|entry| entry.peer == peer)
+        .cloned()
+        .collect()
+}
+
+// Collects the types of the messages received from the target for the given duration.
+async fn collect_inbound(
+    synth_node: &mut SyntheticNode,
+    target: SocketAddr,
+    duration: Duration,
+    message_types: &mut Vec<u16>,
+) {
+    let deadline = Instant::now() + duration;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        match timeout(remaining, synth_node.recv_inbound_message()).await {
+            Ok((source, message)) if source == target => {
+                message_types.push(message.header().message_type())
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        protocol::{
+            codecs::message::Payload,
+            proto::{tm_ping::PingType, MessageType, TmPing},
+        },
+        tools::{capture::read_capture, config::SynthNodeCfg},
+        wait_until,
+    };
+
+    const PINGS: u32 = 3;
+    const CONNECTION_TIMEOUT: Duration = Duration::from_secs(1);
+
+    fn ping(r#type: PingType, seq: u32) -> Payload {
+        Payload::TmPing(TmPing {
+            r#type: r#type as i32,
+            seq: Some(seq),
+            ping_time: None,
+            net_time: None,
+        })
+    }
+
+    // Starts a synthetic node which answers every ping with a pong and returns its address.
+    async fn start_pong_responder() -> SocketAddr {
+        let cfg = SynthNodeCfg {
+            handshake: None,
+            ..Default::default()
+        };
+        let mut responder = SyntheticNode::new(&cfg).await.unwrap();
+        let addr = responder.start_listening().await.unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (source, message) = responder.recv_message().await;
+                if let Payload::TmPing(TmPing { seq, .. }) = message.payload {
+                    let _ = responder.unicast(source, ping(PingType::PtPong, seq.unwrap()));
+                }
+            }
+        });
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn replay_against_synthetic_node() {
+        let dir = TempDir::new().unwrap();
+        let capture_path = dir.path().join("capture.jsonl");
+
+        // Record a session.
+        let target = start_pong_responder().await;
+        let cfg = SynthNodeCfg {
+            handshake: None,
+            capture: Some(capture_path.clone()),
+            ..Default::default()
+        };
+        let mut recorder = SyntheticNode::new(&cfg).await.unwrap();
+        recorder.connect(target).await.unwrap();
+        wait_until!(CONNECTION_TIMEOUT, recorder.is_connected(target));
+
+        for seq in 0..PINGS {
+            recorder
+                .unicast(target, ping(PingType::PtPing, seq))
+                .unwrap();
+            let (_, message) = recorder
+                .recv_message_timeout(CONNECTION_TIMEOUT)
+                .await
+                .unwrap();
+            assert!(matches!(message.payload, Payload::TmPing(..)));
+        }
+        recorder.shut_down().await;
+
+        let entries = entries_for_peer(&read_capture(&capture_path).unwrap(), target);
+        assert_eq!(entries.len(), 2 * PINGS as usize);
+
+        // Replay it against a fresh target.
+        let target = start_pong_responder().await;
+        let cfg = SynthNodeCfg {
+            handshake: None,
+            ..Default::default()
+        };
+        let mut replayer = SyntheticNode::new(&cfg).await.unwrap();
+        replayer.connect(target).await.unwrap();
+        wait_until!(CONNECTION_TIMEOUT, replayer.is_connected(target));
+
+        let replay_cfg = ReplayCfg {
+            time_scale: 0.0,
+            settle_time: Duration::from_millis(200),
+        };
+        let report = replay(&mut replayer, target, &entries, &replay_cfg)
+            .await
+            .unwrap();
+
+        assert_eq!(report.frames_sent, PINGS as usize);
+        assert_eq!(
+            report.replayed_inbound,
+            vec![MessageType::MtPing as u16; PINGS as usize]
+        );
+        assert!(report.is_match(), "{:?}", report.diff());
+
+        replayer.shut_down().await;
+    }
+
+    #[test]
+    fn time_scale_validation() {
+        for time_scale in [0.0, 0.5, 1.0, 10.0, MAX_TIME_SCALE] {
+            let cfg = ReplayCfg {
+                time_scale,
+                ..Default::default()
+            };
+            assert!(cfg.validate().is_ok(), "{time_scale}");
+        }
+
+        // The largest delay doesn't overflow with the largest scale.
+        let longest_delay = Duration::from_micros(u64::MAX);
+        assert!(longest_delay.mul_f64(MAX_TIME_SCALE) > longest_delay);
+
+        for time_scale in [-1.0, f64::NAN, f64::INFINITY, MAX_TIME_SCALE * 2.0, 1e300] {
+            let cfg = ReplayCfg {
+                time_scale,
+                ..Default::default()
+            };
+            assert_eq!(
+                cfg.validate().unwrap_err().kind(),
+                io::ErrorKind::InvalidInput,
+                "{time_scale}"
+            );
+        }
+    }
+
+    #[test]
+    fn report_diff() {
+        let report = ReplayReport {
+            frames_sent: 0,
+            recorded_inbound: vec![3, 3, 15, 41],
+            replayed_inbound: vec![3, 41, 3, 64],
+        };
+
+        assert!(!report.is_match());
+        assert_eq!(
+            report.diff(),
+            vec![
+                MessageTypeDiff {
+                    message_type: 15,
+                    recorded: 1,
+                    replayed: 0
+                },
+                MessageTypeDiff {
+                    message_type: 64,
+                    recorded: 0,
+                    replayed: 1
+                },
+            ]
+        );
+    }
+}
@@ -71,7 +71,7 @@ async fn get_account_info(
     execute_rpc(rpc_url, &build_account_info_request(account)).await
 }
 
-async fn get_server_info(rpc_url: &str) -> anyhow::Result<RpcResponse<ResultResponse>> {
+pub async fn get_server_info(rpc_url: &str) -> anyhow::Result<RpcResponse<ResultResponse>> {
     let request: RpcRequest<Option<()>> = RpcRequest {
         id: String::from("1"),
         method: String::from("server_info"),
@@ -225,6 +225,7 @@ pub struct ResultResponse {
 #[derive(Debug, Deserialize)]
 pub struct ServerInfoResponse {
     pub server_state: String,
+    pub pubkey_node: String,
 }
 
 #[derive(Debug, Deserialize)]
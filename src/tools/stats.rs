@@ -0,0 +1,396 @@
+//! Per-connection message statistics of synthetic nodes, if enabled (see
+//! [`SynthNodeCfg::stats`](crate::tools::config::SynthNodeCfg::stats)).
+//!
+//! Besides being kept per connection (see [`Stats::get`]), the statistics are also exported
+//! through the [`metrics`] crate, so that they are available to any installed recorder:
+//!
+//! - [`METRIC_FRAMES`] and [`METRIC_BYTES`] counters, labelled with `peer`, `direction` and
+//!   `message_type`,
+//! - [`METRIC_DECODE_ERRORS`] and [`METRIC_SKIPPED_FRAMES`] counters, labelled with `peer`,
+//! - [`METRIC_INTER_ARRIVAL`] histogram (in milliseconds) of the inbound messages, labelled with
+//!   `peer` and `message_type`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use metrics::{Counter, Histogram};
+
+use crate::{protocol::proto::MessageType, tools::capture::Direction};
+
+pub const METRIC_FRAMES: &str = "xrpl_frames";
+pub const METRIC_BYTES: &str = "xrpl_bytes";
+pub const METRIC_DECODE_ERRORS: &str = "xrpl_decode_errors";
+pub const METRIC_SKIPPED_FRAMES: &str = "xrpl_skipped_frames";
+pub const METRIC_INTER_ARRIVAL: &str = "xrpl_inter_arrival_ms";
+
+/// The statistics of a single message type (or of the unframed bytes) in one direction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageStats {
+    pub frames: u64,
+    pub bytes: u64,
+    pub first_seen: Option<Instant>,
+    pub last_seen: Option<Instant>,
+    /// The shortest interval between two consecutive frames.
+    pub min_inter_arrival: Option<Duration>,
+}
+
+impl MessageStats {
+    // Records a frame, returning the interval since the previous one.
+    fn record(&mut self, bytes: usize, now: Instant) -> Option<Duration> {
+        let inter_arrival = self.last_seen.map(|last_seen| now - last_seen);
+
+        self.frames += 1;
+        self.bytes += bytes as u64;
+        self.first_seen.get_or_insert(now);
+        self.last_seen = Some(now);
+        if let Some(interval) = inter_arrival {
+            self.min_inter_arrival = Some(
+                self.min_inter_arrival
+                    .map_or(interval, |min| min.min(interval)),
+            );
+        }
+
+        inter_arrival
+    }
+}
+
+/// The statistics of a single connection.
+#[derive(Debug, Clone)]
+pub struct ConnectionStats {
+    pub connected_at: Instant,
+    /// Set once the connection is closed.
+    pub disconnected_at: Option<Instant>,
+    /// The inbound frames, per message type.
+    pub inbound: BTreeMap<u16, MessageStats>,
+    /// The outbound frames, per message type.
+    pub outbound: BTreeMap<u16, MessageStats>,
+    /// Arbitrary bytes sent with
+    /// [`unicast_bytes`](crate::tools::synth_node::SyntheticNode::unicast_bytes).
+    pub outbound_unframed: MessageStats,
+    /// The inbound frames which couldn't be decoded.
+    pub decode_errors: u64,
+    /// The inbound frames which were skipped for exceeding the maximum frame size (see
+    /// [`OversizedFramePolicy::Skip`](crate::protocol::codecs::message::OversizedFramePolicy::Skip)).
+    pub skipped_frames: u64,
+}
+
+impl ConnectionStats {
+    fn new() -> Self {
+        Self {
+            connected_at: Instant::now(),
+            disconnected_at: None,
+            inbound: Default::default(),
+            outbound: Default::default(),
+            outbound_unframed: Default::default(),
+            decode_errors: 0,
+            skipped_frames: 0,
+        }
+    }
+
+    /// Returns the statistics of the given message type.
+    pub fn message(&self, direction: Direction, message_type: MessageType) -> MessageStats {
+        let per_type = match direction {
+            Direction::Inbound => &self.inbound,
+            Direction::Outbound => &self.outbound,
+        };
+
+        per_type
+            .get(&(message_type as u16))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the total number of frames in the given direction.
+    pub fn frames(&self, direction: Direction) -> u64 {
+        match direction {
+            Direction::Inbound => self.inbound.values().map(|stats| stats.frames).sum(),
+            Direction::Outbound => {
+                self.outbound
+                    .values()
+                    .map(|stats| stats.frames)
+                    .sum::<u64>()
+                    + self.outbound_unframed.frames
+            }
+        }
+    }
+
+    /// Returns the total number of bytes in the given direction.
+    pub fn bytes(&self, direction: Direction) -> u64 {
+        match direction {
+            Direction::Inbound => self.inbound.values().map(|stats| stats.bytes).sum(),
+            Direction::Outbound => {
+                self.outbound.values().map(|stats| stats.bytes).sum::<u64>()
+                    + self.outbound_unframed.bytes
+            }
+        }
+    }
+
+    /// Returns the average number of frames of the given type per minute, while the connection
+    /// was established.
+    pub fn per_minute(&self, direction: Direction, message_type: MessageType) -> f64 {
+        let duration = match self.disconnected_at {
+            Some(disconnected_at) => disconnected_at - self.connected_at,
+            None => self.connected_at.elapsed(),
+        };
+        let minutes = duration.as_secs_f64() / 60.0;
+
+        self.message(direction, message_type).frames as f64 / minutes
+    }
+}
+
+/// The statistics of all the connections of a node, kept per peer address.
+///
+/// The statistics of a connection remain available after it's closed and are reset once the peer
+/// reconnects.
+#[derive(Clone, Default)]
+pub struct Stats {
+    connections: Arc<Mutex<HashMap<SocketAddr, Arc<Mutex<PeerState>>>>>,
+}
+
+impl Stats {
+    /// Returns a handle recording the statistics of the connection with the given peer.
+    pub fn for_peer(&self, peer: SocketAddr) -> PeerStats {
+        let mut connections = self.connections.lock().unwrap();
+        let state = connections
+            .entry(peer)
+            .or_insert_with(|| PeerState::new(peer));
+        if state.lock().unwrap().stats.disconnected_at.is_some() {
+            *state = PeerState::new(peer);
+        }
+
+        PeerStats {
+            state: state.clone(),
+        }
+    }
+
+    /// Marks the connection with the given peer as closed.
+    pub fn disconnect(&self, peer: SocketAddr) {
+        if let Some(state) = self.connections.lock().unwrap().get(&peer) {
+            state
+                .lock()
+                .unwrap()
+                .stats
+                .disconnected_at
+                .get_or_insert_with(Instant::now);
+        }
+    }
+
+    /// Returns a snapshot of the statistics of the connection with the given peer.
+    pub fn get(&self, peer: SocketAddr) -> Option<ConnectionStats> {
+        self.connections
+            .lock()
+            .unwrap()
+            .get(&peer)
+            .map(|state| state.lock().unwrap().stats.clone())
+    }
+
+    /// Returns a snapshot of the statistics of all the connections.
+    pub fn snapshot(&self) -> HashMap<SocketAddr, ConnectionStats> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(peer, state)| (*peer, state.lock().unwrap().stats.clone()))
+            .collect()
+    }
+}
+
+// The statistics of a single connection, along with its metrics handles.
+struct PeerState {
+    stats: ConnectionStats,
+    metrics: PeerMetrics,
+}
+
+impl PeerState {
+    fn new(peer: SocketAddr) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            stats: ConnectionStats::new(),
+            metrics: PeerMetrics::new(peer),
+        }))
+    }
+}
+
+// The metrics of a single connection; the handles are registered once, so that recording a frame
+// doesn't allocate the labels.
+struct PeerMetrics {
+    peer: String,
+    decode_errors: Counter,
+    skipped_frames: Counter,
+    per_type: HashMap<(Direction, Option<u16>), MessageMetrics>,
+}
+
+struct MessageMetrics {
+    frames: Counter,
+    bytes: Counter,
+    // Only kept for the inbound messages.
+    inter_arrival: Option<Histogram>,
+}
+
+impl PeerMetrics {
+    fn new(peer: SocketAddr) -> Self {
+        let peer = peer.to_string();
+
+        let decode_errors =
+            metrics::register_counter!(METRIC_DECODE_ERRORS, "peer" => peer.clone());
+        let skipped_frames =
+            metrics::register_counter!(METRIC_SKIPPED_FRAMES, "peer" => peer.clone());
+
+        Self {
+            peer,
+            decode_errors,
+            skipped_frames,
+            per_type: Default::default(),
+        }
+    }
+
+    fn message(&mut self, direction: Direction, message_type: Option<u16>) -> &MessageMetrics {
+        let peer = &self.peer;
+
+        self.per_type
+            .entry((direction, message_type))
+            .or_insert_with(|| {
+                let direction_label = match direction {
+                    Direction::Inbound => "inbound",
+                    Direction::Outbound => "outbound",
+                };
+                let message_type = message_type_label(message_type);
+
+                MessageMetrics {
+                    frames: metrics::register_counter!(
+                        METRIC_FRAMES,
+                        "peer" => peer.clone(),
+                        "direction" => direction_label,
+                        "message_type" => message_type.clone()
+                    ),
+                    bytes: metrics::register_counter!(
+                        METRIC_BYTES,
+                        "peer" => peer.clone(),
+                        "direction" => direction_label,
+                        "message_type" => message_type.clone()
+                    ),
+                    inter_arrival: (direction == Direction::Inbound).then(|| {
+                        metrics::register_histogram!(
+                            METRIC_INTER_ARRIVAL,
+                            "peer" => peer.clone(),
+                            "message_type" => message_type
+                        )
+                    }),
+                }
+            })
+    }
+}
+
+/// A handle recording the statistics of a single connection.
+#[derive(Clone)]
+pub struct PeerStats {
+    state: Arc<Mutex<PeerState>>,
+}
+
+impl PeerStats {
+    /// Records a single frame; `message_type` isn't set for unframed bytes.
+    pub fn record_frame(&self, direction: Direction, message_type: Option<u16>, bytes: usize) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+
+        let stats = &mut state.stats;
+        let message_stats = match (direction, message_type) {
+            (Direction::Inbound, Some(message_type)) => {
+                stats.inbound.entry(message_type).or_default()
+            }
+            (Direction::Outbound, Some(message_type)) => {
+                stats.outbound.entry(message_type).or_default()
+            }
+            (_, None) => &mut stats.outbound_unframed,
+        };
+        let inter_arrival = message_stats.record(bytes, now);
+
+        let metrics = state.metrics.message(direction, message_type);
+        metrics.frames.increment(1);
+        metrics.bytes.increment(bytes as u64);
+        if let (Some(histogram), Some(interval)) = (&metrics.inter_arrival, inter_arrival) {
+            histogram.record(interval.as_secs_f64() * 1000.0);
+        }
+    }
+
+    /// Records an inbound frame which couldn't be decoded.
+    pub fn record_decode_error(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.stats.decode_errors += 1;
+        state.metrics.decode_errors.increment(1);
+    }
+
+    /// Records an inbound frame which was skipped for being oversized.
+    pub fn record_skipped_frame(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.stats.skipped_frames += 1;
+        state.metrics.skipped_frames.increment(1);
+    }
+}
+
+// Message types are labelled by name, unknown ones by number.
+fn message_type_label(message_type: Option<u16>) -> String {
+    match message_type {
+        Some(message_type) => match MessageType::from_i32(message_type as i32) {
+            Some(known) => format!("{known:?}"),
+            None => message_type.to_string(),
+        },
+        None => "unframed".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_frames() {
+        let stats = Stats::default();
+        let peer = "127.0.0.1:51235".parse().unwrap();
+        let peer_stats = stats.for_peer(peer);
+
+        peer_stats.record_frame(
+            Direction::Inbound,
+            Some(MessageType::MtEndpoints as u16),
+            20,
+        );
+        peer_stats.record_frame(
+            Direction::Inbound,
+            Some(MessageType::MtEndpoints as u16),
+            30,
+        );
+        peer_stats.record_frame(Direction::Outbound, Some(MessageType::MtPing as u16), 10);
+        peer_stats.record_frame(Direction::Outbound, None, 7);
+        peer_stats.record_decode_error();
+        peer_stats.record_skipped_frame();
+
+        let snapshot = stats.get(peer).unwrap();
+        let endpoints = snapshot.message(Direction::Inbound, MessageType::MtEndpoints);
+        assert_eq!(endpoints.frames, 2);
+        assert_eq!(endpoints.bytes, 50);
+        assert!(endpoints.min_inter_arrival.is_some());
+        assert!(snapshot.per_minute(Direction::Inbound, MessageType::MtEndpoints) > 0.0);
+
+        assert_eq!(snapshot.frames(Direction::Inbound), 2);
+        assert_eq!(snapshot.frames(Direction::Outbound), 2);
+        assert_eq!(snapshot.bytes(Direction::Outbound), 17);
+        assert_eq!(snapshot.decode_errors, 1);
+        assert_eq!(snapshot.skipped_frames, 1);
+        assert!(stats.get("127.0.0.1:1".parse().unwrap()).is_none());
+
+        // The statistics outlive the connection, but are reset on reconnection.
+        stats.disconnect(peer);
+        let snapshot = stats.get(peer).unwrap();
+        assert!(snapshot.disconnected_at.is_some());
+        assert_eq!(snapshot.frames(Direction::Inbound), 2);
+
+        stats.for_peer(peer);
+        let reconnected = stats.get(peer).unwrap();
+        assert!(reconnected.connected_at > snapshot.connected_at);
+        assert!(reconnected.disconnected_at.is_none());
+        assert_eq!(reconnected.frames(Direction::Inbound), 0);
+    }
+}
@@ -5,25 +5,29 @@ use std::{
 };
 
 use pea2pea::{
-    protocols::{Handshake, Reading, Writing},
+    protocols::{Disconnect, Handshake, Reading, Writing},
     Pea2Pea,
 };
 use tokio::{
     net::TcpSocket,
     sync::{mpsc, mpsc::Receiver, oneshot},
-    time::timeout,
+    time::{sleep, timeout},
 };
-use tracing::trace;
+use tracing::{trace, warn};
 
 use crate::{
     protocol::{
         codecs::message::{BinaryMessage, Payload},
+        features::ProtocolFeatures,
+        handshake::{HandshakeOutcome, PeerInfo},
+        reading::InboundMessage,
         writing::MessageOrBytes,
     },
     tools::{
         config::SynthNodeCfg,
         constants::{EXPECTED_RESULT_TIMEOUT, SYNTH_NODE_QUEUE_DEPTH},
         inner_node::InnerNode,
+        stats::ConnectionStats,
     },
 };
 
@@ -39,21 +43,24 @@ pub fn enable_tracing() {
 
 pub struct SyntheticNode {
     inner: InnerNode,
-    receiver: Receiver<(SocketAddr, BinaryMessage)>,
+    receiver: Receiver<(SocketAddr, InboundMessage)>,
 }
 
 impl SyntheticNode {
-    pub async fn new(config: &SynthNodeCfg) -> Self {
+    /// Creates the node; fails if the TLS configuration is invalid or the capture file can't be
+    /// created.
+    pub async fn new(config: &SynthNodeCfg) -> io::Result<Self> {
         let (sender, receiver) = mpsc::channel(SYNTH_NODE_QUEUE_DEPTH);
-        let inner = InnerNode::new(config, sender).await;
+        let inner = InnerNode::new(config, sender).await?;
 
         if config.handshake.is_some() {
             inner.enable_handshake().await;
         }
         inner.enable_reading().await;
         inner.enable_writing().await;
+        inner.enable_disconnect().await;
 
-        Self { inner, receiver }
+        Ok(Self { inner, receiver })
     }
 
     /// Starts listening for inbound connections.
@@ -64,6 +71,9 @@ impl SyntheticNode {
     }
 
     /// Connects to the target address.
+    ///
+    /// A handshake declined by the target is an error; use
+    /// [`connect_with_outcome`](Self::connect_with_outcome) to tell it apart from other failures.
     pub async fn connect(&self, target: SocketAddr) -> io::Result<()> {
         self.inner.connect(target).await
     }
@@ -73,12 +83,30 @@ impl SyntheticNode {
         self.inner.connect_from(target, socket).await
     }
 
+    /// Connects to the target address.
+    ///
+    /// A handshake declined by the target is reported as [`HandshakeOutcome::Rejected`], while
+    /// other failures are errors.
+    pub async fn connect_with_outcome(&self, target: SocketAddr) -> io::Result<HandshakeOutcome> {
+        self.inner.connect_with_outcome(target).await
+    }
+
+    /// Connects to the target address using specified socket, reporting the handshake outcome
+    /// like [`connect_with_outcome`](Self::connect_with_outcome).
+    pub async fn connect_from_with_outcome(
+        &self,
+        target: SocketAddr,
+        socket: TcpSocket,
+    ) -> io::Result<HandshakeOutcome> {
+        self.inner.connect_from_with_outcome(target, socket).await
+    }
+
     pub fn unicast(
         &self,
         addr: SocketAddr,
         message: Payload,
     ) -> io::Result<oneshot::Receiver<io::Result<()>>> {
-        trace!(parent: self.inner.node().span(), "unicast send msg to {addr}: {:?}", message);
+        trace!(parent: self.inner.node().span(), "unicast send msg to {addr}: {message}");
         self.inner.unicast(addr, MessageOrBytes::Payload(message))
     }
 
@@ -91,10 +119,47 @@ impl SyntheticNode {
         self.inner.unicast(addr, MessageOrBytes::Bytes(bytes))
     }
 
+    /// Sends the bytes one at a time, waiting the given interval in between, e.g. to trickle a
+    /// framed message.
+    ///
+    /// Returns an error once the connection is closed.
+    pub async fn unicast_bytes_trickled(
+        &self,
+        addr: SocketAddr,
+        bytes: Vec<u8>,
+        interval: Duration,
+    ) -> io::Result<()> {
+        for (i, byte) in bytes.into_iter().enumerate() {
+            if i != 0 {
+                sleep(interval).await;
+            }
+            self.unicast_bytes(addr, vec![byte])?
+                .await
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))??;
+        }
+
+        Ok(())
+    }
+
     /// Reads a message from the inbound (internal) queue of the node.
     ///
-    /// Messages are sent to the queue when unfiltered by the message filter.
+    /// Messages are sent to the queue when unfiltered by the message filter. If lazy decoding
+    /// is enabled, the messages are decoded here and those which can't be decoded are skipped.
     pub async fn recv_message(&mut self) -> (SocketAddr, BinaryMessage) {
+        loop {
+            let (source, message) = self.recv_inbound_message().await;
+            match message.into_binary_message() {
+                Ok(message) => return (source, message),
+                Err(e) => {
+                    warn!(parent: self.inner.node().span(), "skipping a message from {source}: {e}")
+                }
+            }
+        }
+    }
+
+    /// Reads a message from the inbound (internal) queue of the node, as handed out by the
+    /// codec, i.e. without decoding it if lazy decoding is enabled.
+    pub async fn recv_inbound_message(&mut self) -> (SocketAddr, InboundMessage) {
         match self.receiver.recv().await {
             Some(message) => message,
             None => panic!("all senders dropped!"),
@@ -140,6 +205,28 @@ impl SyntheticNode {
         self.inner.is_connected_ip(addr)
     }
 
+    /// Returns the serialized public key presented during the handshake.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.inner.crypto.public_key()
+    }
+
+    /// Returns the information presented and verified during the handshake with the given address,
+    /// as long as it's connected.
+    pub fn peer_info(&self, addr: SocketAddr) -> Option<PeerInfo> {
+        self.inner.peer_info(addr)
+    }
+
+    /// Returns the protocol features negotiated with the given address during the handshake.
+    pub fn negotiated_features(&self, addr: SocketAddr) -> Option<ProtocolFeatures> {
+        self.inner.negotiated_features(addr)
+    }
+
+    /// Returns a snapshot of the message statistics of the connection with the given address, if
+    /// enabled (see [`SynthNodeCfg::stats`]).
+    pub fn connection_stats(&self, addr: SocketAddr) -> Option<ConnectionStats> {
+        self.inner.stats.as_ref()?.get(addr)
+    }
+
     pub async fn expect_message(&mut self, check: &dyn Fn(&BinaryMessage) -> bool) -> bool {
         timeout(EXPECTED_RESULT_TIMEOUT, async {
             loop {
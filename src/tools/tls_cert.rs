@@ -1,21 +1,162 @@
 //! Based on https://github.com/sfackler/rust-openssl/blob/master/openssl/examples/mk_certs.rs.
 
+use std::{fs, io, path::Path, sync::OnceLock};
+
 use openssl::{
     asn1::Asn1Time,
+    bn::{BigNum, MsbOption},
+    ec::{EcGroup, EcKey},
     error::ErrorStack,
     hash::MessageDigest,
+    nid::Nid,
     pkey::{PKey, PKeyRef, Private},
     rsa::Rsa,
-    x509::X509,
+    x509::{
+        extension::{BasicConstraints, KeyUsage},
+        X509Builder, X509Name, X509Ref, X509,
+    },
 };
 
-/// Make a CA certificate and private key
-pub fn mk_ca_cert() -> Result<(X509, PKey<Private>), ErrorStack> {
-    let rsa = Rsa::generate(2048)?;
-    let key_pair = PKey::from_rsa(rsa)?;
+// The subject fields shared by the certificates, similar to the ones of rippled's certificates.
+const COUNTRY: &str = "US";
+const STATE: &str = "California";
+const LOCALITY: &str = "San Francisco";
+const ORGANIZATION: &str = "Ripple";
+
+// The common names of the certificates.
+const CA_COMMON_NAME: &str = "Ripple CA";
+const COMMON_NAME: &str = "rippled";
+
+// The validity period of the certificates.
+const VALIDITY_DAYS: u32 = 365;
+
+/// The type of the certificate keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CertKeyType {
+    #[default]
+    Rsa2048,
+    /// An EC key on the P-256 (prime256v1) curve.
+    EcP256,
+}
+
+impl CertKeyType {
+    fn generate(self) -> Result<PKey<Private>, ErrorStack> {
+        match self {
+            Self::Rsa2048 => PKey::from_rsa(Rsa::generate(2048)?),
+            Self::EcP256 => {
+                let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+                PKey::from_ec_key(EcKey::generate(&group)?)
+            }
+        }
+    }
+}
+
+/// The certificate and private key presented in the TLS handshake.
+///
+/// Generating the keys is slow, so the identity can be shared by many synthetic nodes (see
+/// [`TlsIdentity::shared`]) or persisted to disk (see [`TlsIdentity::load_or_generate`]).
+#[derive(Clone)]
+pub struct TlsIdentity {
+    pub cert: X509,
+    pub key_pair: PKey<Private>,
+}
 
+impl TlsIdentity {
+    /// Generates a certificate signed by a freshly generated CA, both with keys of the given type.
+    pub fn generate(key_type: CertKeyType) -> Result<Self, ErrorStack> {
+        let (ca_cert, ca_key_pair) = mk_ca_cert(key_type)?;
+        let (cert, key_pair) = mk_ca_signed_cert(&ca_cert, &ca_key_pair, key_type)?;
+
+        Ok(Self { cert, key_pair })
+    }
+
+    /// Returns the identity generated on the first use and shared within the process.
+    pub fn shared() -> Self {
+        static SHARED: OnceLock<TlsIdentity> = OnceLock::new();
+
+        SHARED
+            .get_or_init(|| {
+                Self::generate(Default::default()).expect("unable to generate the TLS identity")
+            })
+            .clone()
+    }
+
+    /// Loads the identity from the PEM file at the given path; if there is no such file, a new
+    /// identity is generated and saved there.
+    pub fn load_or_generate(path: &Path, key_type: CertKeyType) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(pem) => Ok(Self {
+                cert: X509::from_pem(&pem)?,
+                key_pair: PKey::private_key_from_pem(&pem)?,
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let identity = Self::generate(key_type)?;
+                identity.save(path)?;
+                Ok(identity)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Saves the certificate followed by the private key to a PEM file at the given path.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut pem = self.cert.to_pem()?;
+        pem.extend_from_slice(&self.key_pair.private_key_to_pem_pkcs8()?);
+
+        fs::write(path, pem)
+    }
+}
+
+// Builds the certificate subject with the given common name.
+fn mk_name(common_name: &str) -> Result<X509Name, ErrorStack> {
+    let mut name = X509Name::builder()?;
+    name.append_entry_by_nid(Nid::COUNTRYNAME, COUNTRY)?;
+    name.append_entry_by_nid(Nid::STATEORPROVINCENAME, STATE)?;
+    name.append_entry_by_nid(Nid::LOCALITYNAME, LOCALITY)?;
+    name.append_entry_by_nid(Nid::ORGANIZATIONNAME, ORGANIZATION)?;
+    name.append_entry_by_nid(Nid::COMMONNAME, common_name)?;
+
+    Ok(name.build())
+}
+
+// Builds a certificate with a random serial number, valid from now on.
+fn mk_cert_builder(
+    subject: &X509Name,
+    key_pair: &PKeyRef<Private>,
+) -> Result<X509Builder, ErrorStack> {
     let mut cert_builder = X509::builder()?;
-    cert_builder.set_pubkey(&key_pair)?;
+    cert_builder.set_version(2)?;
+    let serial_number = {
+        let mut serial = BigNum::new()?;
+        serial.rand(159, MsbOption::MAYBE_ZERO, false)?;
+        serial.to_asn1_integer()?
+    };
+    cert_builder.set_serial_number(&serial_number)?;
+    cert_builder.set_subject_name(subject)?;
+    cert_builder.set_pubkey(key_pair)?;
+    let not_before = Asn1Time::days_from_now(0)?;
+    cert_builder.set_not_before(&not_before)?;
+    let not_after = Asn1Time::days_from_now(VALIDITY_DAYS)?;
+    cert_builder.set_not_after(&not_after)?;
+
+    Ok(cert_builder)
+}
+
+/// Make a CA certificate and private key
+pub fn mk_ca_cert(key_type: CertKeyType) -> Result<(X509, PKey<Private>), ErrorStack> {
+    let key_pair = key_type.generate()?;
+
+    let name = mk_name(CA_COMMON_NAME)?;
+    let mut cert_builder = mk_cert_builder(&name, &key_pair)?;
+    cert_builder.set_issuer_name(&name)?;
+    cert_builder.append_extension(BasicConstraints::new().critical().ca().build()?)?;
+    cert_builder.append_extension(
+        KeyUsage::new()
+            .critical()
+            .key_cert_sign()
+            .crl_sign()
+            .build()?,
+    )?;
 
     cert_builder.sign(&key_pair, MessageDigest::sha256())?;
     let cert = cert_builder.build();
@@ -25,20 +166,50 @@ pub fn mk_ca_cert() -> Result<(X509, PKey<Private>), ErrorStack> {
 
 /// Make a certificate and private key signed by the given CA cert and private key
 pub fn mk_ca_signed_cert(
+    ca_cert: &X509Ref,
     ca_key_pair: &PKeyRef<Private>,
+    key_type: CertKeyType,
 ) -> Result<(X509, PKey<Private>), ErrorStack> {
-    let rsa = Rsa::generate(2048)?;
-    let key_pair = PKey::from_rsa(rsa)?;
+    let key_pair = key_type.generate()?;
 
-    let mut cert_builder = X509::builder()?;
-    cert_builder.set_pubkey(&key_pair)?;
-    let not_before = Asn1Time::days_from_now(0)?;
-    cert_builder.set_not_before(&not_before)?;
-    let not_after = Asn1Time::days_from_now(365)?;
-    cert_builder.set_not_after(&not_after)?;
+    let name = mk_name(COMMON_NAME)?;
+    let mut cert_builder = mk_cert_builder(&name, &key_pair)?;
+    cert_builder.set_issuer_name(ca_cert.subject_name())?;
+    cert_builder.append_extension(BasicConstraints::new().build()?)?;
 
     cert_builder.sign(ca_key_pair, MessageDigest::sha256())?;
     let cert = cert_builder.build();
 
     Ok((cert, key_pair))
 }
+
+#[cfg(test)]
+mod tests {
+    use openssl::x509::X509NameRef;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn persisted_identity() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("identity.pem");
+
+        let identity = TlsIdentity::load_or_generate(&path, CertKeyType::EcP256).unwrap();
+        let loaded = TlsIdentity::load_or_generate(&path, CertKeyType::EcP256).unwrap();
+        assert_eq!(
+            loaded.cert.to_der().unwrap(),
+            identity.cert.to_der().unwrap()
+        );
+        assert!(loaded.key_pair.public_eq(&identity.key_pair));
+        assert!(loaded.key_pair.ec_key().is_ok());
+
+        // The certificate is issued by the CA to rippled.
+        let common_name = |name: &X509NameRef| {
+            let entry = name.entries_by_nid(Nid::COMMONNAME).next().unwrap();
+            entry.data().as_utf8().unwrap().to_string()
+        };
+        assert_eq!(common_name(loaded.cert.subject_name()), COMMON_NAME);
+        assert_eq!(common_name(loaded.cert.issuer_name()), CA_COMMON_NAME);
+    }
+}
@@ -0,0 +1,111 @@
+//! Slow writing, used to keep the peer waiting (see [`Trickle`](crate::protocol::handshake::Trickle)).
+
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{sleep, Sleep},
+};
+
+/// A stream which, unless stopped, writes a single byte per interval.
+pub struct TrickleStream<S> {
+    inner: S,
+    // The time between the writes; unset if the stream isn't trickling.
+    interval: Option<Duration>,
+    // The wait before the next write.
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> TrickleStream<S> {
+    pub fn new(inner: S, interval: Option<Duration>) -> Self {
+        Self {
+            inner,
+            interval,
+            delay: None,
+        }
+    }
+
+    /// Writes at full speed from now on.
+    pub fn stop(&mut self) {
+        self.interval = None;
+        self.delay = None;
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TrickleStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TrickleStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        let Some(interval) = this.interval else {
+            return Pin::new(&mut this.inner).poll_write(cx, buf);
+        };
+
+        if let Some(delay) = this.delay.as_mut() {
+            ready!(delay.as_mut().poll(cx));
+        }
+        let written = ready!(Pin::new(&mut this.inner).poll_write(cx, &buf[..buf.len().min(1)]))?;
+        this.delay = Some(Box::pin(sleep(interval)));
+
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{duplex, AsyncReadExt, AsyncWriteExt},
+        time::Instant,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn trickle_and_stop() {
+        const INTERVAL: Duration = Duration::from_millis(50);
+
+        let (client, mut server) = duplex(64);
+        let mut stream = TrickleStream::new(client, Some(INTERVAL));
+
+        // The first byte is written right away, followed by one byte per interval.
+        let start = Instant::now();
+        stream.write_all(b"abc").await.unwrap();
+        assert!(start.elapsed() >= 2 * INTERVAL);
+
+        let start = Instant::now();
+        stream.stop();
+        stream.write_all(b"def").await.unwrap();
+        assert!(start.elapsed() < INTERVAL);
+
+        let mut buf = [0u8; 6];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"abcdef");
+    }
+}